@@ -0,0 +1,220 @@
+//! Встроенная панель: по экземпляру на монитор вдоль его верхнего края.
+//! Показывает столы монитора и заголовок его сфокусированного окна.
+
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::xproto::*;
+
+use crate::config::Colors;
+use crate::layout::Rect;
+use crate::tabbar::latin1;
+
+pub const BAR_HEIGHT: u32 = 18;
+
+/// Отступ текста от краёв ячейки стола.
+const PADDING: i16 = 6;
+
+pub struct BarWorkspace {
+    pub label: String,
+    /// Основной стол монитора.
+    pub current: bool,
+    /// Стол входит в вид монитора.
+    pub visible: bool,
+    /// На столе есть окна.
+    pub occupied: bool,
+    /// На столе есть окно, просящее внимания.
+    pub urgent: bool,
+}
+
+/// Содержимое панели одного монитора.
+pub struct BarContent {
+    pub rect: Rect,
+    /// Монитор сейчас текущий.
+    pub selected: bool,
+    pub workspaces: Vec<BarWorkspace>,
+    pub title: String,
+}
+
+/// Окна панелей; как и полосы вкладок, переиспользуются между
+/// перерисовками.
+pub struct Bars {
+    colors: Colors,
+    gc: Gcontext,
+    ascent: i16,
+    descent: i16,
+    char_width: i16,
+    windows: Vec<Window>,
+    contents: Vec<BarContent>,
+}
+
+impl Bars {
+    pub fn new(
+        conn: &impl Connection,
+        screen: &Screen,
+        colors: Colors,
+    ) -> Result<Self, ReplyOrIdError> {
+        let font = conn.generate_id()?;
+        conn.open_font(font, b"fixed")?;
+        let info = conn.query_font(font)?.reply()?;
+
+        let gc = conn.generate_id()?;
+        conn.create_gc(
+            gc,
+            screen.root,
+            &CreateGCAux::new()
+                .font(font)
+                .foreground(colors.text)
+                .graphics_exposures(0),
+        )?;
+        conn.close_font(font)?;
+
+        Ok(Bars {
+            colors,
+            gc,
+            ascent: info.font_ascent,
+            descent: info.font_descent,
+            char_width: info.max_bounds.character_width,
+            windows: Vec::new(),
+            contents: Vec::new(),
+        })
+    }
+
+    /// Новые цвета вступают в силу со следующей перерисовкой.
+    pub fn set_colors(&mut self, colors: Colors) {
+        self.colors = colors;
+    }
+
+    /// Показывает по панели на каждый элемент `contents`.
+    pub fn update(
+        &mut self,
+        conn: &impl Connection,
+        screen: &Screen,
+        contents: Vec<BarContent>,
+    ) -> Result<(), ReplyOrIdError> {
+        while self.windows.len() < contents.len() {
+            let window = conn.generate_id()?;
+            conn.create_window(
+                screen.root_depth,
+                window,
+                screen.root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &CreateWindowAux::new()
+                    .override_redirect(1)
+                    .background_pixel(self.colors.background)
+                    .event_mask(EventMask::EXPOSURE),
+            )?;
+            self.windows.push(window);
+        }
+        for window in self.windows.drain(contents.len()..) {
+            conn.destroy_window(window)?;
+        }
+
+        for (&window, content) in self.windows.iter().zip(&contents) {
+            conn.configure_window(
+                window,
+                &ConfigureWindowAux::new()
+                    .x(content.rect.x)
+                    .y(content.rect.y)
+                    .width(content.rect.width.max(1))
+                    .height(content.rect.height.max(1))
+                    .stack_mode(StackMode::ABOVE),
+            )?;
+            conn.map_window(window)?;
+        }
+
+        self.contents = contents;
+        for i in 0..self.windows.len() {
+            self.draw(conn, i)?;
+        }
+        Ok(())
+    }
+
+    /// Перерисовывает панель после Expose; чужие окна игнорируются.
+    pub fn redraw(&self, conn: &impl Connection, window: Window) -> Result<(), ReplyOrIdError> {
+        match self.windows.iter().position(|&w| w == window) {
+            Some(i) => self.draw(conn, i),
+            None => Ok(()),
+        }
+    }
+
+    fn draw(&self, conn: &impl Connection, index: usize) -> Result<(), ReplyOrIdError> {
+        let window = self.windows[index];
+        let content = &self.contents[index];
+        let height = content.rect.height as i16;
+        let baseline = (height - self.ascent - self.descent) / 2 + self.ascent;
+
+        let colors = &self.colors;
+        conn.change_gc(self.gc, &ChangeGCAux::new().foreground(colors.background))?;
+        conn.poly_fill_rectangle(
+            window,
+            self.gc,
+            &[Rectangle {
+                x: 0,
+                y: 0,
+                width: content.rect.width as u16,
+                height: height as u16,
+            }],
+        )?;
+
+        let mut x = 0;
+        for workspace in &content.workspaces {
+            let text = latin1(&workspace.label);
+            let width = text.len() as i16 * self.char_width + 2 * PADDING;
+            let color = if workspace.current && content.selected {
+                colors.focused
+            } else if workspace.urgent {
+                colors.urgent
+            } else if workspace.visible {
+                colors.unfocused
+            } else {
+                colors.background
+            };
+            conn.change_gc(self.gc, &ChangeGCAux::new().foreground(color))?;
+            conn.poly_fill_rectangle(
+                window,
+                self.gc,
+                &[Rectangle {
+                    x,
+                    y: 0,
+                    width: width as u16,
+                    height: height as u16,
+                }],
+            )?;
+            conn.change_gc(
+                self.gc,
+                &ChangeGCAux::new().foreground(colors.text).background(color),
+            )?;
+            // Занятые столы отмечены квадратиком в углу, как в dwm.
+            if workspace.occupied {
+                conn.poly_fill_rectangle(
+                    window,
+                    self.gc,
+                    &[Rectangle {
+                        x: x + 1,
+                        y: 1,
+                        width: 3,
+                        height: 3,
+                    }],
+                )?;
+            }
+            conn.image_text8(window, self.gc, x + PADDING, baseline, &text)?;
+            x += width;
+        }
+
+        conn.change_gc(
+            self.gc,
+            &ChangeGCAux::new()
+                .foreground(colors.text)
+                .background(colors.background),
+        )?;
+        let text = latin1(&content.title);
+        conn.image_text8(window, self.gc, x + 2 * PADDING, baseline, &text)?;
+        Ok(())
+    }
+}
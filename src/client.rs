@@ -0,0 +1,177 @@
+use x11rb::properties::WmSizeHints;
+use x11rb::protocol::xproto::Window;
+
+use crate::layout::Rect;
+
+/// Состояние управляемого окна.
+pub struct Client {
+    /// Геометрия, которую окно выбрало само; применяется в плавающем режиме.
+    pub float_geometry: Rect,
+    /// Окно занимает свою ячейку, но сохраняет желаемый размер
+    /// и центрируется в ней (pseudo_tiled в bspwm).
+    pub pseudo_tiled: bool,
+    pub size_hints: Option<WmSizeHints>,
+    /// Монитор окна; теги относятся к его столам.
+    pub monitor: usize,
+    /// Теги окна: бит `i` — окно видно на столе `i`.
+    pub tags: u32,
+    /// Окно видно на всех столах своего монитора.
+    pub sticky: bool,
+    /// Стол, опубликованный в `_NET_WM_DESKTOP`.
+    pub desktop: Option<u32>,
+    /// Толщина рамки, опубликованная в `_NET_FRAME_EXTENTS`.
+    pub frame: Option<u32>,
+    /// Окно вне тайлинга: живёт в `float_geometry` поверх тайловых окон.
+    pub floating: bool,
+    /// Окно держится над остальными (`_NET_WM_STATE_ABOVE`).
+    pub above: bool,
+    /// Окно на весь монитор без рамки поверх панели; прежняя геометрия
+    /// не теряется, потому что раскладка её не трогает.
+    pub fullscreen: bool,
+    /// Окно развёрнуто по горизонтали и по вертикали.
+    pub maximized: (bool, bool),
+    /// Геометрия и плавающее состояние до разворачивания.
+    pub unmaximized: Option<(Rect, bool)>,
+    /// Родитель диалога из WM_TRANSIENT_FOR.
+    pub transient_for: Option<Window>,
+    /// Имя блокнота, если окно — блокнот; такие окна живут вне столов.
+    pub scratchpad: Option<String>,
+    /// Окно спрятано независимо от тегов и вида.
+    pub hidden: bool,
+    /// Окно принимает ввод от менеджера (поле input в WM_HINTS).
+    pub input: bool,
+    /// Окно само берёт фокус по сообщению WM_TAKE_FOCUS.
+    pub take_focus: bool,
+    /// Окно просит внимания (флаг urgency в WM_HINTS) и ещё не получило фокус.
+    pub urgent: bool,
+    /// Окно отображено менеджером.
+    pub mapped: bool,
+    /// Сколько UnmapNotify ещё придёт от снятий с экрана самим менеджером;
+    /// остальные значат, что окно убрало себя само.
+    pub pending_unmaps: u32,
+    /// Процесс приложения из `_NET_WM_PID`.
+    pub pid: Option<u32>,
+    /// Экземпляр и класс приложения из WM_CLASS.
+    pub instance: String,
+    pub class: String,
+    /// Толщина рамки из правила при 96 DPI вместо `border_width`.
+    pub border: Option<u32>,
+    /// Окно по правилу не получает фокус при появлении.
+    pub no_focus: bool,
+}
+
+impl Client {
+    pub fn new(float_geometry: Rect, size_hints: Option<WmSizeHints>) -> Self {
+        Client {
+            float_geometry,
+            pseudo_tiled: false,
+            size_hints,
+            monitor: 0,
+            tags: 0,
+            sticky: false,
+            desktop: None,
+            frame: None,
+            floating: false,
+            transient_for: None,
+            above: false,
+            fullscreen: false,
+            maximized: (false, false),
+            unmaximized: None,
+            scratchpad: None,
+            hidden: false,
+            input: true,
+            take_focus: false,
+            urgent: false,
+            mapped: false,
+            pending_unmaps: 0,
+            pid: None,
+            instance: String::new(),
+            class: String::new(),
+            border: None,
+            no_focus: false,
+        }
+    }
+
+    /// Желаемый размер из WM_NORMAL_HINTS, иначе исходная геометрия окна.
+    fn preferred_size(&self) -> (u32, u32) {
+        let hinted = self
+            .size_hints
+            .and_then(|hints| hints.size.map(|(_, w, h)| (w, h)).or(hints.base_size))
+            .filter(|&(w, h)| w > 0 && h > 0)
+            .map(|(w, h)| (w as u32, h as u32));
+        hinted.unwrap_or((self.float_geometry.width, self.float_geometry.height))
+    }
+
+    /// Окно расставляет раскладка: оно не плавает и не на весь экран.
+    pub fn tiled(&self) -> bool {
+        !self.floating && !self.fullscreen
+    }
+
+    /// Размер, ограниченный минимумом и максимумом из WM_NORMAL_HINTS;
+    /// без минимума им служит базовый размер.
+    pub fn constrain(&self, width: u32, height: u32) -> (u32, u32) {
+        let Some(hints) = self.size_hints else {
+            return (width, height);
+        };
+        let (mut width, mut height) = (width as i32, height as i32);
+        if let Some((max_width, max_height)) = hints.max_size {
+            if max_width > 0 {
+                width = width.min(max_width);
+            }
+            if max_height > 0 {
+                height = height.min(max_height);
+            }
+        }
+        if let Some((min_width, min_height)) = hints.min_size.or(hints.base_size) {
+            width = width.max(min_width);
+            height = height.max(min_height);
+        }
+        (width.max(1) as u32, height.max(1) as u32)
+    }
+
+    /// Наибольший не превышающий заданного размер вида «база + n × шаг»
+    /// по шагу из WM_NORMAL_HINTS; базой без базового размера служит минимум.
+    pub fn apply_increments(&self, width: u32, height: u32) -> (u32, u32) {
+        let Some(hints) = self.size_hints else {
+            return (width, height);
+        };
+        let Some((width_inc, height_inc)) = hints.size_increment else {
+            return (width, height);
+        };
+        let (base_width, base_height) = hints.base_size.or(hints.min_size).unwrap_or((0, 0));
+        let fit = |size: u32, base: i32, inc: i32| {
+            let (size, base) = (size as i32, base.max(0));
+            if inc <= 1 || size <= base {
+                return size as u32;
+            }
+            (base + (size - base) / inc * inc) as u32
+        };
+        (
+            fit(width, base_width, width_inc),
+            fit(height, base_height, height_inc),
+        )
+    }
+
+    /// Окно фиксированного размера: минимум совпадает с максимумом.
+    pub fn fixed_size(&self) -> bool {
+        self.size_hints.is_some_and(|hints| {
+            hints.min_size.is_some_and(|(w, h)| w > 0 && h > 0) && hints.min_size == hints.max_size
+        })
+    }
+
+    /// Геометрия окна в ячейке `slot` с учётом псевдотайлинга.
+    pub fn fit(&self, slot: Rect) -> Rect {
+        if !self.pseudo_tiled {
+            return slot;
+        }
+        let (width, height) = self.preferred_size();
+        let width = width.min(slot.width);
+        let height = height.min(slot.height);
+        Rect {
+            x: slot.x + ((slot.width - width) / 2) as i32,
+            y: slot.y + ((slot.height - height) / 2) as i32,
+            width,
+            height,
+        }
+    }
+}
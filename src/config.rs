@@ -0,0 +1,526 @@
+//! Настройки менеджера. Значения по умолчанию собраны в `Default`,
+//! а файл `config.toml` переопределяет любые из них. Файл ищется в
+//! `$XDG_CONFIG_HOME/rewm`, `~/.config/rewm` и `/etc/rewm`, если путь
+//! не задан ключом `--config`.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use inotify::{Inotify, WatchMask};
+use serde::Deserialize;
+use toml::Spanned;
+use x11rb::protocol::xproto::ModMask;
+
+use crate::layout::{LayoutMode, MASTER_FACTOR_RANGE};
+use crate::rules::Rule;
+use crate::workspace::MAX_WORKSPACES;
+use crate::{ipc, keys};
+
+/// Именованный блокнот (scratchpad): окно, которое привязка
+/// вызывает поверх столов и прячет обратно.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scratchpad {
+    pub name: String,
+    /// Команда, запускающая окно блокнота, если его ещё нет.
+    pub command: String,
+    /// Экземпляр или класс WM_CLASS, по которому узнаётся окно блокнота.
+    pub class: String,
+}
+
+/// Профиль мониторов («docked», «laptop»): какие столы на каком выходе.
+/// Применяется сам, когда набор подключённых выходов совпадает с
+/// выходами профиля, или командой `profile <имя>`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub name: String,
+    /// Имена первых столов по выходам RandR.
+    pub outputs: BTreeMap<String, Vec<String>>,
+}
+
+/// Кому отдаётся фокус при появлении нового окна.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NewWindowFocus {
+    /// Всегда; ради окна, ушедшего на скрытый стол, стол открывается.
+    Always,
+    /// Только окнам, появившимся на видимом столе.
+    CurrentWorkspace,
+    /// Никогда: новое окно лишь помечается urgent.
+    Never,
+}
+
+/// Модификатор привязок менеджера.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Modifier {
+    Control,
+    /// Обычно Alt.
+    Mod1,
+    Mod3,
+    /// Обычно Super, клавиша с логотипом.
+    Mod4,
+    Mod5,
+}
+
+impl Modifier {
+    pub fn mask(self) -> ModMask {
+        match self {
+            Modifier::Control => ModMask::CONTROL,
+            Modifier::Mod1 => ModMask::M1,
+            Modifier::Mod3 => ModMask::M3,
+            Modifier::Mod4 => ModMask::M4,
+            Modifier::Mod5 => ModMask::M5,
+        }
+    }
+}
+
+/// Цвета панели, полос вкладок и обзора (0xRRGGBB).
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    /// Текущий стол и вкладка с фокусом.
+    pub focused: u32,
+    /// Видимые столы и активная вкладка без фокуса.
+    pub unfocused: u32,
+    /// Фон и остальные столы и вкладки.
+    pub background: u32,
+    pub text: u32,
+    /// Столы с окнами, просящими внимания.
+    pub urgent: u32,
+    /// Подсказка предвыбора места для нового окна.
+    pub presel: u32,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            focused: 0x285577,
+            unfocused: 0x5f676a,
+            background: 0x222222,
+            text: 0xffffff,
+            urgent: 0x900000,
+            presel: 0x4c7899,
+        }
+    }
+}
+
+/// Настройки оконного менеджера. Значения по умолчанию собраны в
+/// `Default`, как в config.h у dwm.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Перечитывать файл настроек самому после каждого его сохранения;
+    /// включение действует с запуска менеджера.
+    pub watch_config: bool,
+    /// Команды оболочки, запускаемые при старте менеджера; перечитывание
+    /// настроек их не повторяет.
+    pub autostart: Vec<String>,
+    /// Модификатор привязок и перетаскивания окон мышью.
+    pub modifier: Modifier,
+    /// Привязки клавиш поверх привязок по умолчанию: сочетание вроде
+    /// `Mod4+Shift+q` (`Mod` — модификатор `modifier`) и команда, как
+    /// у `rewm msg`, например `workspace 3` или `exec alacritty`.
+    /// Команда `none` снимает привязку.
+    pub bindings: BTreeMap<String, String>,
+    /// Раскладка новых столов.
+    pub layout: LayoutMode,
+    /// Начальная доля мастер-области, от 0.1 до 0.9.
+    pub master_factor: f32,
+    /// Шаг, с которым привязки меняют долю мастер-области.
+    pub master_factor_step: f32,
+    /// Начальное число окон в мастер-области (master–stack и колода).
+    pub nmaster: usize,
+    /// Число рабочих столов, от 1 до 32; переключаются привязками Mod4+1..9.
+    pub workspaces: usize,
+    /// Имена первых столов; остальные называются по номеру.
+    pub workspace_names: Vec<String>,
+    /// Повторное нажатие привязки текущего стола возвращает на предыдущий.
+    pub auto_back_and_forth: bool,
+    /// Дописывать к имени стола класс его сфокусированного окна.
+    pub autoname_workspaces: bool,
+    /// Фокус у окна под указателем; иначе окно фокусируется щелчком.
+    pub focus_follows_mouse: bool,
+    /// Не отдавать фокус окнам, появившимся или попросившим его, пока
+    /// пользователь работал в другом окне: такие окна помечаются urgent.
+    pub focus_stealing_prevention: bool,
+    /// Фокус новых окон; защита от кражи фокуса действует поверх.
+    pub new_window_focus: NewWindowFocus,
+    /// При смене фокуса с клавиатуры переносить указатель в центр окна
+    /// с фокусом, а при переходе на пустой монитор — в центр монитора.
+    /// Иначе при фокусе по указателю следующее движение мыши вернёт фокус.
+    pub warp_pointer: bool,
+    /// Шаг при 96 DPI, с которым привязки двигают плавающее окно и меняют
+    /// его размер.
+    pub float_step: u32,
+    /// На каком расстоянии в пикселях перетаскиваемое окно прилипает
+    /// к краям мониторов, панели и других окон; 0 — не прилипает.
+    pub snap_distance: u32,
+    /// Подгонять размер тайловых окон под шаг из WM_NORMAL_HINTS, чтобы
+    /// терминалы не обрезали строки и столбцы.
+    pub size_increments: bool,
+    /// Остаток ячейки после подгонки под шаг делится поровну по краям
+    /// окна; иначе он остаётся щелью справа и снизу.
+    pub center_increment_slack: bool,
+    /// Зазор между тайловыми окнами и от них до краёв рабочей области
+    /// при 96 DPI; 0 — без зазоров.
+    pub gap: u32,
+    /// Толщина рамки окон при 96 DPI; 0 — без рамок.
+    pub border_width: u32,
+    /// Цвета рамки (0xRRGGBB): окна с фокусом, остальных окон и
+    /// окон, просящих внимания.
+    pub border_focused: u32,
+    pub border_unfocused: u32,
+    pub border_urgent: u32,
+    pub colors: Colors,
+    /// Плотность пикселей всех мониторов; `None` — по физическому
+    /// размеру выходов из RandR. Панель и полосы вкладок масштабируются
+    /// относительно 96 DPI.
+    pub dpi: Option<f32>,
+    /// Показывать встроенную панель на каждом мониторе.
+    pub show_bar: bool,
+    /// Показывать панель только на основном мониторе.
+    pub bar_primary_only: bool,
+    /// Прокрутка колеса над пустым рабочим столом листает столы.
+    pub root_scroll: bool,
+    /// Профили мониторов; автоматически применяется первый подходящий.
+    pub profiles: Vec<Profile>,
+    /// Правила для новых окон; действуют все подходящие, а из
+    /// противоречащих друг другу — первое.
+    pub rules: Vec<Rule>,
+    /// Блокноты; первый вызывается привязкой Mod4+Shift+Return.
+    pub scratchpads: Vec<Scratchpad>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            watch_config: false,
+            autostart: Vec::new(),
+            modifier: Modifier::Mod4,
+            bindings: BTreeMap::new(),
+            layout: LayoutMode::Horizontal,
+            master_factor: 0.5,
+            master_factor_step: 0.05,
+            nmaster: 1,
+            workspaces: 9,
+            workspace_names: Vec::new(),
+            auto_back_and_forth: false,
+            autoname_workspaces: false,
+            focus_follows_mouse: false,
+            focus_stealing_prevention: true,
+            new_window_focus: NewWindowFocus::CurrentWorkspace,
+            warp_pointer: false,
+            float_step: 20,
+            snap_distance: 10,
+            size_increments: true,
+            center_increment_slack: false,
+            gap: 0,
+            border_width: 2,
+            border_focused: 0x4c7899,
+            border_unfocused: 0x333333,
+            border_urgent: 0x900000,
+            colors: Colors::default(),
+            dpi: None,
+            show_bar: true,
+            bar_primary_only: false,
+            root_scroll: true,
+            profiles: Vec::new(),
+            rules: Vec::new(),
+            scratchpads: vec![Scratchpad {
+                name: "term".into(),
+                command: "alacritty --class scratchpad".into(),
+                class: "scratchpad".into(),
+            }],
+        }
+    }
+}
+
+/// Где искать файл настроек, по порядку.
+pub fn search_paths() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .collect();
+    dirs.dedup();
+    dirs.into_iter()
+        .map(|dir| dir.join("rewm"))
+        .chain([PathBuf::from("/etc/rewm")])
+        .map(|dir| dir.join("config.toml"))
+        .collect()
+}
+
+/// Файл настроек: заданный явно или первый найденный из `search_paths`.
+pub fn find(explicit: Option<&Path>) -> Option<PathBuf> {
+    match explicit {
+        Some(path) => Some(path.to_path_buf()),
+        None => search_paths().into_iter().find(|path| path.is_file()),
+    }
+}
+
+impl Config {
+    /// Читает файл настроек (см. `find`); без файла действуют значения
+    /// по умолчанию.
+    pub fn read(explicit: Option<&Path>) -> Result<Self, String> {
+        let Some(path) = find(explicit) else {
+            return Ok(Config::default());
+        };
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("не удалось прочитать {}: {}", path.display(), e))?;
+        let mut config: Config =
+            toml::from_str(&text).map_err(|e| format!("ошибка в {}: {}", path.display(), e))?;
+        // Без столов мониторам нечего показывать, а больше их не вмещают
+        // теги; мастер-область и стопка не должны исчезать.
+        config.workspaces = config.workspaces.clamp(1, MAX_WORKSPACES);
+        config.master_factor = config
+            .master_factor
+            .clamp(*MASTER_FACTOR_RANGE.start(), *MASTER_FACTOR_RANGE.end());
+        println!("Загружена конфигурация {}", path.display());
+        Ok(config)
+    }
+
+    /// Как `read`, но файл с ошибкой сообщается и пропускается.
+    pub fn load(explicit: Option<&Path>) -> Self {
+        Config::read(explicit).unwrap_or_else(|e| {
+            println!("Настройки по умолчанию: {}", e);
+            Config::default()
+        })
+    }
+}
+
+/// Прокомментированный файл со значениями по умолчанию.
+const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
+
+/// Записывает файл со значениями по умолчанию в `explicit` или в первый
+/// путь `search_paths` и возвращает путь. Существующий файл
+/// перезаписывается только при `force`.
+pub fn write_default(explicit: Option<&Path>, force: bool) -> Result<PathBuf, String> {
+    let path = match explicit {
+        Some(path) => path.to_path_buf(),
+        None => search_paths()
+            .into_iter()
+            .next()
+            .ok_or("не найден каталог настроек")?,
+    };
+    if path.exists() && !force {
+        return Err(format!(
+            "{} уже существует; перезаписать: rewm --generate-config --force",
+            path.display()
+        ));
+    }
+    path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, DEFAULT_CONFIG))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Сколько ждать, пока сохранение файла закончит порождать события.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Следит за файлом настроек и зовёт `changed` после каждого его
+/// сохранения. Слежка идёт за каталогом: редакторы часто сохраняют файл,
+/// подменяя его новым.
+pub fn watch(path: &Path, changed: impl Fn() + Send + 'static) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let name = path.file_name().map(OsString::from);
+    let mut inotify = Inotify::init()?;
+    inotify
+        .watches()
+        .add(&dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)?;
+    thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        loop {
+            let saved = match inotify.read_events_blocking(&mut buffer) {
+                Ok(mut events) => events.any(|event| event.name == name.as_deref()),
+                Err(e) => {
+                    println!("Слежение за настройками прекращено: {}", e);
+                    return;
+                }
+            };
+            if saved {
+                thread::sleep(WATCH_DEBOUNCE);
+                while inotify.read_events(&mut buffer).is_ok() {}
+                changed();
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Места в файле настроек, которые проверяет `check`.
+#[derive(Deserialize)]
+struct Locations {
+    #[serde(default)]
+    bindings: BTreeMap<Spanned<String>, Spanned<String>>,
+    border_focused: Option<Spanned<u32>>,
+    border_unfocused: Option<Spanned<u32>>,
+    border_urgent: Option<Spanned<u32>>,
+    #[serde(default)]
+    colors: BTreeMap<String, Spanned<u32>>,
+    workspaces: Option<Spanned<usize>>,
+    master_factor: Option<Spanned<f32>>,
+    master_factor_step: Option<Spanned<f32>>,
+}
+
+/// Проверяет текст файла настроек и возвращает замечания с номерами
+/// строк: ошибки разбора и неизвестные поля, неразборчивые сочетания
+/// и команды привязок, повторы одного сочетания, цвета вне 0xRRGGBB
+/// число столов вне 1..=32 и доля мастер-области вне 0.1..=0.9.
+pub fn check(text: &str) -> Vec<(usize, String)> {
+    let line = |offset: usize| text[..offset].matches('\n').count() + 1;
+    let config: Config = match toml::from_str(text) {
+        Ok(config) => config,
+        Err(e) => {
+            let at = e.span().map_or(1, |span| line(span.start));
+            return vec![(at, e.message().trim().to_string())];
+        }
+    };
+    let Ok(locations) = toml::from_str::<Locations>(text) else {
+        return Vec::new();
+    };
+
+    let mut problems = Vec::new();
+    let mut bindings: Vec<_> = locations.bindings.into_iter().collect();
+    bindings.sort_by_key(|(combo, _)| combo.span().start);
+    let mut seen: Vec<((ModMask, u32), &str)> = Vec::new();
+    for (combo, command) in &bindings {
+        let at = line(combo.span().start);
+        match keys::parse_combo(combo.get_ref(), config.modifier.mask()) {
+            Ok(key) => match seen.iter().find(|(other, _)| *other == key) {
+                Some((_, first)) => problems.push((
+                    at,
+                    format!("{}: то же сочетание, что и {}", combo.get_ref(), first),
+                )),
+                None => seen.push((key, combo.get_ref())),
+            },
+            Err(e) => problems.push((at, format!("{}: {}", combo.get_ref(), e))),
+        }
+        let command_text = command.get_ref().trim();
+        if command_text != "none" && !ipc::is_command(command_text) {
+            problems.push((
+                line(command.span().start),
+                format!("{}: неизвестная команда {}", combo.get_ref(), command_text),
+            ));
+        }
+    }
+
+    let borders = [
+        ("border_focused", locations.border_focused),
+        ("border_unfocused", locations.border_unfocused),
+        ("border_urgent", locations.border_urgent),
+    ];
+    let colors = borders
+        .into_iter()
+        .filter_map(|(name, color)| Some((name.to_string(), color?)))
+        .chain(
+            locations
+                .colors
+                .into_iter()
+                .map(|(name, color)| (format!("colors.{}", name), color)),
+        );
+    for (name, color) in colors {
+        if *color.get_ref() > 0xffffff {
+            problems.push((
+                line(color.span().start),
+                format!("{}: цвет {:#x} не в виде 0xRRGGBB", name, color.get_ref()),
+            ));
+        }
+    }
+    if let Some(workspaces) = locations.workspaces
+        && !(1..=MAX_WORKSPACES).contains(workspaces.get_ref())
+    {
+        problems.push((
+            line(workspaces.span().start),
+            format!(
+                "workspaces: число столов должно быть от 1 до {}, а не {}",
+                MAX_WORKSPACES,
+                workspaces.get_ref()
+            ),
+        ));
+    }
+    if let Some(factor) = locations.master_factor
+        && !MASTER_FACTOR_RANGE.contains(factor.get_ref())
+    {
+        problems.push((
+            line(factor.span().start),
+            format!(
+                "master_factor: доля должна быть от {} до {}, а не {}",
+                MASTER_FACTOR_RANGE.start(),
+                MASTER_FACTOR_RANGE.end(),
+                factor.get_ref()
+            ),
+        ));
+    }
+    // Шаг больше всего диапазона долей не оставляет промежуточных значений.
+    let widest = MASTER_FACTOR_RANGE.end() - MASTER_FACTOR_RANGE.start();
+    if let Some(step) = locations.master_factor_step
+        && !(*step.get_ref() > 0.0 && *step.get_ref() <= widest)
+    {
+        problems.push((
+            line(step.span().start),
+            format!(
+                "master_factor_step: шаг должен быть больше 0 и не больше {:.1}, а не {}",
+                widest,
+                step.get_ref()
+            ),
+        ));
+    }
+    problems.sort_by_key(|&(at, _)| at);
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_clean() {
+        assert_eq!(check(DEFAULT_CONFIG), []);
+        let config: Config = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.workspaces, Config::default().workspaces);
+    }
+
+    #[test]
+    fn check_reports_lines() {
+        let text = r#"gap = 4
+workspaces = 0
+border_focused = 0x1000000
+
+[bindings]
+"Mod+Return" = "exec alacritty"
+"Mod4+Return" = "exec foot"
+"Mod+Hyper+x" = "frobnicate"
+"#;
+        let problems = check(text);
+        let lines: Vec<usize> = problems.iter().map(|&(at, _)| at).collect();
+        assert_eq!(lines, [2, 3, 7, 8, 8], "{:?}", problems);
+        assert!(problems[2].1.contains("то же сочетание, что и Mod+Return"));
+        assert!(problems[4].1.contains("неизвестная команда frobnicate"));
+    }
+
+    #[test]
+    fn check_reports_parse_errors() {
+        assert_eq!(check("gap = 1\nno_such_option = true\n")[0].0, 2);
+        assert_eq!(check("gap = \n").len(), 1);
+    }
+
+    #[test]
+    fn check_reports_master_factor_out_of_range() {
+        let problems = check("master_factor = 1.5\nmaster_factor_step = 0\n");
+        let lines: Vec<usize> = problems.iter().map(|&(at, _)| at).collect();
+        assert_eq!(lines, [1, 2], "{:?}", problems);
+        assert!(check("master_factor = 0.9\nmaster_factor_step = 0.1\n").is_empty());
+    }
+}
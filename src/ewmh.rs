@@ -0,0 +1,410 @@
+//! Свойства EWMH: те, что менеджер публикует для панелей и пейджеров,
+//! и те, что он читает у окон приложений.
+
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::layout::Rect;
+
+x11rb::atom_manager! {
+    pub Atoms: AtomsCookie {
+        UTF8_STRING,
+        MANAGER,
+        WM_PROTOCOLS,
+        WM_DELETE_WINDOW,
+        WM_TAKE_FOCUS,
+        WM_STATE,
+        WM_WINDOW_ROLE,
+        _NET_SUPPORTED,
+        _NET_SUPPORTING_WM_CHECK,
+        _NET_WM_NAME,
+        _NET_ACTIVE_WINDOW,
+        _NET_CLOSE_WINDOW,
+        _NET_MOVERESIZE_WINDOW,
+        _NET_RESTACK_WINDOW,
+        _NET_WM_MOVERESIZE,
+        _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_NUMBER_OF_DESKTOPS,
+        _NET_CURRENT_DESKTOP,
+        _NET_DESKTOP_NAMES,
+        _NET_WM_DESKTOP,
+        _NET_STARTUP_ID,
+        _NET_WM_PID,
+        _NET_WM_USER_TIME,
+        _NET_WM_USER_TIME_WINDOW,
+        _NET_WM_STATE,
+        _NET_WM_STATE_STICKY,
+        _NET_WM_STATE_ABOVE,
+        _NET_WM_STATE_FULLSCREEN,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_DEMANDS_ATTENTION,
+        _NET_WORKAREA,
+        _NET_WM_STRUT,
+        _NET_WM_STRUT_PARTIAL,
+        _NET_FRAME_EXTENTS,
+        _NET_REQUEST_FRAME_EXTENTS,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_DESKTOP,
+        _NET_WM_WINDOW_TYPE_DOCK,
+        _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_UTILITY,
+        _NET_WM_WINDOW_TYPE_SPLASH,
+        _NET_WM_WINDOW_TYPE_NOTIFICATION,
+        _REWM_COMMAND,
+        _REWM_REPLY,
+    }
+}
+
+/// Объявляет менеджер по EWMH: окно `_NET_SUPPORTING_WM_CHECK` с именем
+/// менеджера, ссылка на него с корневого окна и список поддерживаемых
+/// свойств в `_NET_SUPPORTED`.
+pub fn advertise(
+    conn: &impl Connection,
+    screen: &Screen,
+    atoms: &Atoms,
+) -> Result<(), ReplyOrIdError> {
+    let check = conn.generate_id()?;
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        check,
+        screen.root,
+        -1,
+        -1,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new().override_redirect(1),
+    )?;
+    for window in [screen.root, check] {
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            atoms._NET_SUPPORTING_WM_CHECK,
+            AtomEnum::WINDOW,
+            &[check],
+        )?;
+    }
+    conn.change_property8(
+        PropMode::REPLACE,
+        check,
+        atoms._NET_WM_NAME,
+        atoms.UTF8_STRING,
+        b"rewm",
+    )?;
+    let supported = [
+        atoms._NET_SUPPORTED,
+        atoms._NET_SUPPORTING_WM_CHECK,
+        atoms._NET_WM_NAME,
+        atoms._NET_ACTIVE_WINDOW,
+        atoms._NET_CLOSE_WINDOW,
+        atoms._NET_MOVERESIZE_WINDOW,
+        atoms._NET_RESTACK_WINDOW,
+        atoms._NET_WM_MOVERESIZE,
+        atoms._NET_CLIENT_LIST,
+        atoms._NET_CLIENT_LIST_STACKING,
+        atoms._NET_NUMBER_OF_DESKTOPS,
+        atoms._NET_CURRENT_DESKTOP,
+        atoms._NET_DESKTOP_NAMES,
+        atoms._NET_WM_DESKTOP,
+        atoms._NET_WM_USER_TIME,
+        atoms._NET_WM_USER_TIME_WINDOW,
+        atoms._NET_WM_PID,
+        atoms._NET_WM_STATE,
+        atoms._NET_WM_STATE_STICKY,
+        atoms._NET_WM_STATE_ABOVE,
+        atoms._NET_WM_STATE_FULLSCREEN,
+        atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+        atoms._NET_WM_STATE_MAXIMIZED_VERT,
+        atoms._NET_WM_STATE_DEMANDS_ATTENTION,
+        atoms._NET_WORKAREA,
+        atoms._NET_WM_STRUT,
+        atoms._NET_WM_STRUT_PARTIAL,
+        atoms._NET_FRAME_EXTENTS,
+        atoms._NET_REQUEST_FRAME_EXTENTS,
+        atoms._NET_WM_WINDOW_TYPE,
+        atoms._NET_WM_WINDOW_TYPE_DESKTOP,
+        atoms._NET_WM_WINDOW_TYPE_DOCK,
+        atoms._NET_WM_WINDOW_TYPE_DIALOG,
+        atoms._NET_WM_WINDOW_TYPE_UTILITY,
+        atoms._NET_WM_WINDOW_TYPE_SPLASH,
+        atoms._NET_WM_WINDOW_TYPE_NOTIFICATION,
+    ];
+    conn.change_property32(
+        PropMode::REPLACE,
+        screen.root,
+        atoms._NET_SUPPORTED,
+        AtomEnum::ATOM,
+        &supported,
+    )?;
+    Ok(())
+}
+
+/// Публикует управляемые окна: в порядке появления в `_NET_CLIENT_LIST`
+/// и снизу вверх по стопке в `_NET_CLIENT_LIST_STACKING`.
+pub fn set_client_lists(
+    conn: &impl Connection,
+    root: Window,
+    atoms: &Atoms,
+    clients: &[Window],
+    stacking: &[Window],
+) -> Result<(), ReplyOrIdError> {
+    for (property, windows) in [
+        (atoms._NET_CLIENT_LIST, clients),
+        (atoms._NET_CLIENT_LIST_STACKING, stacking),
+    ] {
+        conn.change_property32(PropMode::REPLACE, root, property, AtomEnum::WINDOW, windows)?;
+    }
+    Ok(())
+}
+
+/// Публикует окно с фокусом в `_NET_ACTIVE_WINDOW`; без фокуса — `None`.
+pub fn set_active_window(
+    conn: &impl Connection,
+    root: Window,
+    atoms: &Atoms,
+    window: Option<Window>,
+) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_ACTIVE_WINDOW,
+        AtomEnum::WINDOW,
+        &[window.unwrap_or(x11rb::NONE)],
+    )?;
+    Ok(())
+}
+
+/// Публикует рабочие области столов в `_NET_WORKAREA`: по четвёрке
+/// `x y ширина высота` на стол. Их число задаёт `_NET_NUMBER_OF_DESKTOPS`.
+pub fn set_work_areas(
+    conn: &impl Connection,
+    root: Window,
+    atoms: &Atoms,
+    areas: &[Rect],
+) -> Result<(), ReplyOrIdError> {
+    let data: Vec<u32> = areas
+        .iter()
+        .flat_map(|area| [area.x as u32, area.y as u32, area.width, area.height])
+        .collect();
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_WORKAREA,
+        AtomEnum::CARDINAL,
+        &data,
+    )?;
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_NUMBER_OF_DESKTOPS,
+        AtomEnum::CARDINAL,
+        &[areas.len() as u32],
+    )?;
+    Ok(())
+}
+
+/// Публикует номер текущего стола в `_NET_CURRENT_DESKTOP`.
+pub fn set_current_desktop(
+    conn: &impl Connection,
+    root: Window,
+    atoms: &Atoms,
+    desktop: usize,
+) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_CURRENT_DESKTOP,
+        AtomEnum::CARDINAL,
+        &[desktop as u32],
+    )?;
+    Ok(())
+}
+
+/// Публикует в `_NET_FRAME_EXTENTS` рамку окна: одинаковую со всех
+/// четырёх сторон, заголовков менеджер не рисует.
+pub fn set_frame_extents(
+    conn: &impl Connection,
+    window: Window,
+    atoms: &Atoms,
+    border: u32,
+) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        atoms._NET_FRAME_EXTENTS,
+        AtomEnum::CARDINAL,
+        &[border; 4],
+    )?;
+    Ok(())
+}
+
+/// Стол окна в `_NET_WM_DESKTOP`: на всех столах сразу.
+pub const ALL_DESKTOPS: u32 = u32::MAX;
+
+/// Публикует стол окна в `_NET_WM_DESKTOP`.
+pub fn set_window_desktop(
+    conn: &impl Connection,
+    window: Window,
+    atoms: &Atoms,
+    desktop: u32,
+) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        atoms._NET_WM_DESKTOP,
+        AtomEnum::CARDINAL,
+        &[desktop],
+    )?;
+    Ok(())
+}
+
+/// Страт панели из `_NET_WM_STRUT_PARTIAL`, а без него — из старого
+/// `_NET_WM_STRUT`, который действует вдоль всего края.
+pub fn strut(conn: &impl Connection, window: Window, atoms: &Atoms) -> Option<[u32; 12]> {
+    let values = |property| -> Option<Vec<u32>> {
+        let reply = conn
+            .get_property(false, window, property, AtomEnum::CARDINAL, 0, 12)
+            .ok()?
+            .reply()
+            .ok()?;
+        Some(reply.value32()?.collect())
+    };
+    if let Some(partial) = values(atoms._NET_WM_STRUT_PARTIAL) {
+        return partial.try_into().ok();
+    }
+    let edges = values(atoms._NET_WM_STRUT)?;
+    let &[left, right, top, bottom] = edges.as_slice() else {
+        return None;
+    };
+    let all = u32::MAX;
+    Some([left, right, top, bottom, 0, all, 0, all, 0, all, 0, all])
+}
+
+/// Записывает имена столов в `_NET_DESKTOP_NAMES`: строки UTF-8,
+/// каждая завершена нулевым байтом.
+pub fn set_desktop_names(
+    conn: &impl Connection,
+    root: Window,
+    atoms: &Atoms,
+    names: &[String],
+) -> Result<(), ReplyOrIdError> {
+    let data: Vec<u8> = names
+        .iter()
+        .flat_map(|name| name.bytes().chain([0]))
+        .collect();
+    conn.change_property8(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_DESKTOP_NAMES,
+        atoms.UTF8_STRING,
+        &data,
+    )?;
+    Ok(())
+}
+
+/// Заголовок окна: `_NET_WM_NAME` в UTF-8, а без него — WM_NAME, который
+/// в кодировке STRING хранится в Latin-1.
+pub fn window_name(conn: &impl Connection, window: Window, atoms: &Atoms) -> String {
+    let get = |property: Atom, type_: Atom| {
+        conn.get_property(false, window, property, type_, 0, 256)
+            .ok()?
+            .reply()
+            .ok()
+            .filter(|reply| !reply.value.is_empty())
+    };
+    if let Some(reply) = get(atoms._NET_WM_NAME, atoms.UTF8_STRING) {
+        return String::from_utf8_lossy(&reply.value).into_owned();
+    }
+    match get(AtomEnum::WM_NAME.into(), AtomEnum::ANY.into()) {
+        Some(reply) if reply.type_ == u32::from(AtomEnum::STRING) => {
+            reply.value.iter().map(|&b| char::from(b)).collect()
+        }
+        Some(reply) => String::from_utf8_lossy(&reply.value).into_owned(),
+        None => String::new(),
+    }
+}
+
+/// Время последнего действия пользователя в окне из `_NET_WM_USER_TIME`;
+/// свойство может лежать на отдельном окне `_NET_WM_USER_TIME_WINDOW`.
+pub fn user_time(conn: &impl Connection, window: Window, atoms: &Atoms) -> Option<u32> {
+    let holder = cardinal(
+        conn,
+        window,
+        atoms._NET_WM_USER_TIME_WINDOW,
+        AtomEnum::WINDOW,
+    )
+    .unwrap_or(window);
+    cardinal(conn, holder, atoms._NET_WM_USER_TIME, AtomEnum::CARDINAL)
+}
+
+/// Процесс окна из `_NET_WM_PID`; для окон с других машин он бесполезен.
+pub fn pid(conn: &impl Connection, window: Window, atoms: &Atoms) -> Option<u32> {
+    cardinal(conn, window, atoms._NET_WM_PID, AtomEnum::CARDINAL)
+}
+
+/// Время запуска из `_NET_STARTUP_ID` вида `..._TIME<время>`.
+pub fn startup_time(conn: &impl Connection, window: Window, atoms: &Atoms) -> Option<u32> {
+    let reply = conn
+        .get_property(false, window, atoms._NET_STARTUP_ID, AtomEnum::ANY, 0, 256)
+        .ok()?
+        .reply()
+        .ok()?;
+    let id = String::from_utf8_lossy(&reply.value).into_owned();
+    let (_, time) = id.rsplit_once("_TIME")?;
+    time.parse().ok()
+}
+
+fn cardinal(
+    conn: &impl Connection,
+    window: Window,
+    property: Atom,
+    type_: AtomEnum,
+) -> Option<u32> {
+    let reply = conn
+        .get_property(false, window, property, type_, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?;
+    reply.value32()?.next()
+}
+
+/// Состояния, которые окно запросило в `_NET_WM_STATE` до отображения.
+pub fn wm_state(conn: &impl Connection, window: Window, atoms: &Atoms) -> Vec<Atom> {
+    atom_list(conn, window, atoms._NET_WM_STATE)
+}
+
+/// Типы окна из `_NET_WM_WINDOW_TYPE`, от предпочтительного к запасным.
+pub fn window_types(conn: &impl Connection, window: Window, atoms: &Atoms) -> Vec<Atom> {
+    atom_list(conn, window, atoms._NET_WM_WINDOW_TYPE)
+}
+
+fn atom_list(conn: &impl Connection, window: Window, property: Atom) -> Vec<Atom> {
+    conn.get_property(false, window, property, AtomEnum::ATOM, 0, 32)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .and_then(|reply| Some(reply.value32()?.collect()))
+        .unwrap_or_default()
+}
+
+/// Записывает текущие состояния окна в `_NET_WM_STATE`.
+pub fn set_wm_state(
+    conn: &impl Connection,
+    window: Window,
+    atoms: &Atoms,
+    states: &[Atom],
+) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        atoms._NET_WM_STATE,
+        AtomEnum::ATOM,
+        states,
+    )?;
+    Ok(())
+}
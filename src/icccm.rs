@@ -0,0 +1,174 @@
+//! ICCCM: выбор менеджера WM_Sn, сообщения протоколов из WM_PROTOCOLS,
+//! которые менеджер шлёт поддержавшим их окнам, и состояние окон в WM_STATE.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::ewmh::Atoms;
+
+/// Объявило ли окно протокол `protocol` в WM_PROTOCOLS.
+pub fn supports(conn: &impl Connection, window: Window, atoms: &Atoms, protocol: Atom) -> bool {
+    conn.get_property(false, window, atoms.WM_PROTOCOLS, AtomEnum::ATOM, 0, 32)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .and_then(|reply| Some(reply.value32()?.any(|atom| atom == protocol)))
+        .unwrap_or(false)
+}
+
+/// Шлёт окну сообщение протокола `protocol` с меткой времени `time`.
+pub fn send(
+    conn: &impl Connection,
+    window: Window,
+    atoms: &Atoms,
+    protocol: Atom,
+    time: Timestamp,
+) -> Result<(), ReplyOrIdError> {
+    let event = ClientMessageEvent::new(32, window, atoms.WM_PROTOCOLS, [protocol, time, 0, 0, 0]);
+    conn.send_event(false, window, EventMask::NO_EVENT, event)?;
+    Ok(())
+}
+
+/// Состояние окна в WM_STATE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WmState {
+    /// Окно убрано приложением и менеджером больше не управляется.
+    Withdrawn = 0,
+    Normal = 1,
+    /// Окно спрятано менеджером: на скрытом столе или свёрнуто.
+    Iconic = 3,
+}
+
+/// Записывает WM_STATE окна; значка у окон нет.
+pub fn set_wm_state(
+    conn: &impl Connection,
+    window: Window,
+    atoms: &Atoms,
+    state: WmState,
+) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        window,
+        atoms.WM_STATE,
+        atoms.WM_STATE,
+        &[state as u32, x11rb::NONE],
+    )?;
+    Ok(())
+}
+
+/// Сколько ждать, пока заменяемый менеджер уничтожит окно выбора.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Захватывает выбор менеджера WM_Sn экрана `screen_num` (ICCCM 2.8)
+/// новым окном и возвращает атом выбора. Если выбором владеет другой
+/// менеджер, без `replace` возвращает `None`; с `replace` отбирает выбор
+/// и ждёт, пока прежний владелец уничтожит своё окно.
+pub fn acquire_manager_selection(
+    conn: &impl Connection,
+    screen: &Screen,
+    screen_num: usize,
+    atoms: &Atoms,
+    replace: bool,
+) -> Result<Option<Atom>, ReplyOrIdError> {
+    let name = format!("WM_S{}", screen_num);
+    let selection = conn.intern_atom(false, name.as_bytes())?.reply()?.atom;
+    let previous = conn.get_selection_owner(selection)?.reply()?.owner;
+    if previous != x11rb::NONE && !replace {
+        return Ok(None);
+    }
+
+    let owner = conn.generate_id()?;
+    conn.create_window(
+        x11rb::COPY_DEPTH_FROM_PARENT,
+        owner,
+        screen.root,
+        -1,
+        -1,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new()
+            .override_redirect(1)
+            .event_mask(EventMask::PROPERTY_CHANGE),
+    )?;
+    if previous != x11rb::NONE {
+        conn.change_window_attributes(
+            previous,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+    }
+    let time = server_time(conn, owner)?;
+    conn.set_selection_owner(owner, selection, time)?;
+    if conn.get_selection_owner(selection)?.reply()?.owner != owner {
+        return Ok(None);
+    }
+
+    if previous != x11rb::NONE {
+        println!("Ожидание завершения прежнего менеджера окон");
+        let deadline = Instant::now() + REPLACE_TIMEOUT;
+        loop {
+            match conn.poll_for_event()? {
+                Some(Event::DestroyNotify(event)) if event.window == previous => break,
+                Some(_) => {}
+                None if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                None => {
+                    println!("Прежний менеджер не освободил экран, продолжаем без него");
+                    break;
+                }
+            }
+        }
+    }
+
+    // Клиенты, ждущие менеджера, узнают о нём из сообщения MANAGER.
+    let event = ClientMessageEvent::new(
+        32,
+        screen.root,
+        atoms.MANAGER,
+        [time, selection, owner, 0, 0],
+    );
+    conn.send_event(false, screen.root, EventMask::STRUCTURE_NOTIFY, event)?;
+    Ok(Some(selection))
+}
+
+/// Роль окна из WM_WINDOW_ROLE; пустая, если роль не задана.
+pub fn window_role(conn: &impl Connection, window: Window, atoms: &Atoms) -> String {
+    conn.get_property(
+        false,
+        window,
+        atoms.WM_WINDOW_ROLE,
+        AtomEnum::STRING,
+        0,
+        256,
+    )
+    .ok()
+    .and_then(|cookie| cookie.reply().ok())
+    .map(|reply| String::from_utf8_lossy(&reply.value).into_owned())
+    .unwrap_or_default()
+}
+
+/// Время сервера: его приносит PropertyNotify от пустой дописки свойства
+/// окна, выбравшего PROPERTY_CHANGE.
+fn server_time(conn: &impl Connection, window: Window) -> Result<Timestamp, ReplyOrIdError> {
+    conn.change_property8(
+        PropMode::APPEND,
+        window,
+        AtomEnum::WM_NAME,
+        AtomEnum::STRING,
+        &[],
+    )?;
+    conn.flush()?;
+    loop {
+        if let Event::PropertyNotify(event) = conn.wait_for_event()?
+            && event.window == window
+        {
+            return Ok(event.time);
+        }
+    }
+}
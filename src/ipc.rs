@@ -0,0 +1,258 @@
+//! Управление запущенным менеджером: `rewm msg <команда>`.
+//! Клиент дописывает команду в свойство `_REWM_COMMAND` корневого окна,
+//! завершая её нулевым байтом; менеджер по PropertyNotify забирает все
+//! накопившиеся команды и удаляет свойство. На запросы (`query ...`)
+//! менеджер отвечает свойством `_REWM_REPLY`, которое клиент ждёт.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::*;
+use x11rb::wrapper::ConnectionExt as _;
+
+use crate::Action;
+use crate::layout::Direction;
+use crate::monitor::MonitorTarget;
+
+/// Сколько клиент ждёт ответа на запрос.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Запрос состояния менеджера; ответ печатает `rewm msg`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// Имя основного выхода.
+    PrimaryMonitor,
+    /// Заголовок окна с фокусом.
+    Title,
+    /// Управляемые окна по строке на окно: идентификатор, процесс из
+    /// `_NET_WM_PID` (`-`, если неизвестен), класс и заголовок.
+    Windows,
+    /// Окна, просящие внимания, по строке на окно: идентификатор, стол
+    /// и заголовок.
+    Urgent,
+}
+
+/// Отправляет команду запущенному менеджеру; для запросов печатает ответ.
+pub fn send(command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    let atom = conn.intern_atom(false, b"_REWM_COMMAND")?.reply()?.atom;
+    let query = command.split_whitespace().next() == Some("query");
+    let reply_atom = conn.intern_atom(false, b"_REWM_REPLY")?.reply()?.atom;
+    if query {
+        // Подписка до отправки, чтобы не пропустить быстрый ответ.
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )?;
+    }
+
+    let mut data = command.as_bytes().to_vec();
+    data.push(0);
+    conn.change_property8(PropMode::APPEND, root, atom, AtomEnum::STRING, &data)?;
+    conn.flush()?;
+    if !query {
+        return Ok(());
+    }
+
+    let deadline = Instant::now() + REPLY_TIMEOUT;
+    while Instant::now() < deadline {
+        match conn.poll_for_event()? {
+            Some(Event::PropertyNotify(event))
+                if event.atom == reply_atom && event.state == Property::NEW_VALUE =>
+            {
+                let reply = conn
+                    .get_property(true, root, reply_atom, AtomEnum::ANY, 0, u32::MAX)?
+                    .reply()?;
+                println!("{}", String::from_utf8_lossy(&reply.value));
+                return Ok(());
+            }
+            Some(_) => {}
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    }
+    Err("менеджер не ответил на запрос".into())
+}
+
+/// Публикует ответ на запрос для ждущего клиента.
+pub fn reply(
+    conn: &impl Connection,
+    root: Window,
+    atom: Atom,
+    text: &str,
+) -> Result<(), ReplyOrIdError> {
+    conn.change_property8(
+        PropMode::REPLACE,
+        root,
+        atom,
+        AtomEnum::STRING,
+        text.as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// Забирает команды, накопившиеся в свойстве корневого окна.
+pub fn take_commands(
+    conn: &impl Connection,
+    root: Window,
+    atom: Atom,
+) -> Result<Vec<String>, ReplyOrIdError> {
+    let reply = conn
+        .get_property(true, root, atom, AtomEnum::ANY, 0, u32::MAX)?
+        .reply()?;
+    Ok(reply
+        .value
+        .split(|&b| b == 0)
+        .filter(|command| !command.is_empty())
+        .map(|command| String::from_utf8_lossy(command).into_owned())
+        .collect())
+}
+
+/// Разбирает текстовую команду. Стол в команде задаётся номером с единицы
+/// или именем; `workspace` превращает его в индекс, при необходимости
+/// создавая стол. После `exec` идёт команда оболочки.
+pub fn parse(command: &str, mut workspace: impl FnMut(&str) -> Option<usize>) -> Option<Action> {
+    if let Some(shell) = command.trim_start().strip_prefix("exec ") {
+        let shell = shell.trim();
+        return (!shell.is_empty()).then(|| Action::Spawn(shell.to_string()));
+    }
+    let words: Vec<&str> = command.split_whitespace().collect();
+    let action = match words.as_slice() {
+        ["layout", "next"] => Action::ToggleLayout,
+        ["pseudo-tiled", "toggle"] => Action::TogglePseudoTiled,
+        ["sticky", "toggle"] => Action::ToggleSticky,
+        ["floating", "toggle"] => Action::ToggleFloating,
+        ["above", "toggle"] => Action::ToggleAbove,
+        ["maximize", "toggle"] => Action::ToggleMaximize(true, true),
+        ["maximize", "horizontal"] => Action::ToggleMaximize(true, false),
+        ["maximize", "vertical"] => Action::ToggleMaximize(false, true),
+        ["fullscreen", "toggle"] => Action::ToggleFullscreen,
+        ["center"] => Action::CenterFloating,
+        ["close"] => Action::CloseWindow,
+        ["move", token] => Action::MoveFloating(direction(token)?),
+        ["resize", token] => Action::ResizeFloating(direction(token)?),
+        ["scratchpad", name] => Action::ToggleScratchpad(name.to_string()),
+        ["master", "grow"] => Action::GrowMaster,
+        ["master", "shrink"] => Action::ShrinkMaster,
+        ["master", "inc"] => Action::IncMasterCount,
+        ["master", "dec"] => Action::DecMasterCount,
+        ["mirror"] => Action::Mirror,
+        ["rotate"] => Action::Rotate,
+        ["balance"] => Action::Balance,
+        ["focus", "next"] => Action::FocusNext,
+        ["focus", "prev"] => Action::FocusPrev,
+        ["focus", "urgent"] => Action::FocusUrgent,
+        ["focus", "last"] => Action::FocusLast,
+        ["focus", "older"] => Action::FocusMru(true),
+        ["focus", "newer"] => Action::FocusMru(false),
+        ["focus", token] => Action::FocusDirection(direction(token)?),
+        ["split", "horizontal"] => Action::SplitHorizontal,
+        ["split", "vertical"] => Action::SplitVertical,
+        ["presel", "cancel"] => Action::CancelPreselect,
+        ["presel", token] => Action::Preselect(direction(token)?),
+        ["tabbed", "toggle"] => Action::ToggleTabbed,
+        ["stacked", "toggle"] => Action::ToggleStacked,
+        ["tab", "next"] => Action::NextTab,
+        ["tab", "prev"] => Action::PrevTab,
+        ["workspace", "back-and-forth"] => Action::PreviousWorkspace,
+        ["workspace", "empty"] => Action::EmptyWorkspace,
+        ["move-to-workspace", "empty"] => Action::MoveToEmptyWorkspace(false),
+        ["move-to-workspace", "empty", "follow"] => Action::MoveToEmptyWorkspace(true),
+        ["workspace", n] => Action::ViewWorkspace(workspace(n)?),
+        ["toggle-view", n] => Action::ToggleView(workspace(n)?),
+        ["view-all"] => Action::ViewAll,
+        ["rename-workspace", name @ ..] if !name.is_empty() => {
+            Action::RenameWorkspace(name.join(" "))
+        }
+        ["toggle-tag", n] => Action::ToggleTag(workspace(n)?),
+        ["move-to-workspace", n] => Action::MoveToWorkspace(workspace(n)?, false),
+        ["move-to-workspace", n, "follow"] => Action::MoveToWorkspace(workspace(n)?, true),
+        ["focus-monitor", target] => Action::FocusMonitor(monitor_target(target)),
+        ["move-to-monitor", target] => Action::MoveToMonitor(monitor_target(target)),
+        ["profile", name] => Action::SwitchProfile(name.to_string()),
+        ["query", "primary"] => Action::Query(Query::PrimaryMonitor),
+        ["query", "title"] => Action::Query(Query::Title),
+        ["query", "windows"] => Action::Query(Query::Windows),
+        ["query", "urgent"] => Action::Query(Query::Urgent),
+        ["overview"] => Action::Overview,
+        ["reload"] => Action::Reload,
+        ["quit"] => Action::Quit,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// Разбирается ли команда, если все названные в ней столы существуют.
+pub fn is_command(command: &str) -> bool {
+    parse(command, |_| Some(0)).is_some()
+}
+
+/// Монитор в команде: `next`, `prev`, направление или имя выхода.
+fn monitor_target(token: &str) -> MonitorTarget {
+    match token {
+        "next" => MonitorTarget::Next,
+        "prev" => MonitorTarget::Prev,
+        _ => match direction(token) {
+            Some(direction) => MonitorTarget::Direction(direction),
+            None => MonitorTarget::Name(token.to_string()),
+        },
+    }
+}
+
+fn direction(token: &str) -> Option<Direction> {
+    match token {
+        "left" => Some(Direction::Left),
+        "down" => Some(Direction::Down),
+        "up" => Some(Direction::Up),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Столы по номеру с единицы, без создания новых.
+    fn numbered(token: &str) -> Option<usize> {
+        token.parse::<usize>().ok()?.checked_sub(1)
+    }
+
+    #[test]
+    fn parse_workspace_commands() {
+        assert_eq!(
+            parse("workspace 3", numbered),
+            Some(Action::ViewWorkspace(2))
+        );
+        assert_eq!(
+            parse("  move-to-workspace   2  follow ", numbered),
+            Some(Action::MoveToWorkspace(1, true))
+        );
+        assert_eq!(
+            parse("move-to-workspace 2", numbered),
+            Some(Action::MoveToWorkspace(1, false))
+        );
+        assert_eq!(parse("workspace 0", numbered), None);
+        assert_eq!(parse("workspace web", numbered), None);
+    }
+
+    #[test]
+    fn parse_rejects_unknown_commands() {
+        assert_eq!(parse("", numbered), None);
+        assert_eq!(parse("workspace", numbered), None);
+        assert_eq!(parse("focus sideways", numbered), None);
+        assert_eq!(parse("move-to-workspace 2 stay", numbered), None);
+    }
+
+    #[test]
+    fn parse_keeps_shell_command_whole() {
+        assert_eq!(
+            parse("exec  firefox --new-window ", numbered),
+            Some(Action::Spawn("firefox --new-window".to_string()))
+        );
+        assert_eq!(parse("exec ", numbered), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Раскладка клавиатуры сервера. Привязки задаются keysym, а захват
+//! клавиш и сравнение с нажатиями идут по keycode, поэтому keysym
+//! переводятся в keycode по текущей раскладке.
+
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::xproto::*;
+
+pub const XK_TAB: Keysym = 0xff09;
+pub const XK_RETURN: Keysym = 0xff0d;
+pub const XK_ESCAPE: Keysym = 0xff1b;
+pub const XK_LEFT: Keysym = 0xff51;
+pub const XK_UP: Keysym = 0xff52;
+pub const XK_RIGHT: Keysym = 0xff53;
+pub const XK_DOWN: Keysym = 0xff54;
+pub const XK_ALT_L: Keysym = 0xffe9;
+pub const XK_ALT_R: Keysym = 0xffea;
+
+/// Keysym печатного символа Latin-1 совпадает с кодом символа.
+pub const fn key(c: char) -> Keysym {
+    c as Keysym
+}
+
+/// Keysym клавиш без модификаторов, по строке на keycode.
+pub struct Keymap {
+    min_keycode: Keycode,
+    per_keycode: usize,
+    keysyms: Vec<Keysym>,
+}
+
+impl Keymap {
+    pub fn load(conn: &impl Connection) -> Result<Self, ReplyOrIdError> {
+        let setup = conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let reply = conn.get_keyboard_mapping(min, max - min + 1)?.reply()?;
+        Ok(Keymap {
+            min_keycode: min,
+            per_keycode: reply.keysyms_per_keycode.into(),
+            keysyms: reply.keysyms,
+        })
+    }
+
+    /// Keysym клавиши без модификаторов; заглавная буква приводится
+    /// к строчной, как её и записывают в привязках.
+    pub fn keysym(&self, keycode: Keycode) -> Option<Keysym> {
+        let row = usize::from(keycode.checked_sub(self.min_keycode)?);
+        self.keysyms
+            .get(row * self.per_keycode)
+            .copied()
+            .filter(|&keysym| keysym != 0)
+            .map(lowercase)
+    }
+
+    /// Клавиши, которые без модификаторов дают `keysym`.
+    pub fn keycodes(&self, keysym: Keysym) -> Vec<Keycode> {
+        let keysym = lowercase(keysym);
+        let count = self.keysyms.len() / self.per_keycode.max(1);
+        (0..count)
+            .filter_map(|row| Keycode::try_from(usize::from(self.min_keycode) + row).ok())
+            .filter(|&keycode| self.keysym(keycode) == Some(keysym))
+            .collect()
+    }
+}
+
+fn lowercase(keysym: Keysym) -> Keysym {
+    match keysym {
+        0x41..=0x5a => keysym + 0x20,
+        _ => keysym,
+    }
+}
+
+/// Разбирает сочетание вроде `Mod4+Shift+q`: модификаторы через `+`,
+/// клавиша последней. `Mod` означает модификатор `modkey` из настроек.
+pub fn parse_combo(combo: &str, modkey: ModMask) -> Result<(ModMask, Keysym), String> {
+    let mut parts: Vec<&str> = combo.split('+').map(str::trim).collect();
+    let name = parts
+        .pop()
+        .filter(|name| !name.is_empty())
+        .ok_or("не указана клавиша")?;
+    let mut modifiers = ModMask::from(0u16);
+    for part in parts {
+        modifiers |= match part.to_lowercase().as_str() {
+            "mod" => modkey,
+            "shift" => ModMask::SHIFT,
+            "control" | "ctrl" => ModMask::CONTROL,
+            "mod1" | "alt" => ModMask::M1,
+            "mod3" => ModMask::M3,
+            "mod4" | "super" => ModMask::M4,
+            "mod5" => ModMask::M5,
+            _ => return Err(format!("неизвестный модификатор {}", part)),
+        };
+    }
+    let keysym = keysym_from_name(name).ok_or_else(|| format!("неизвестная клавиша {}", name))?;
+    Ok((modifiers, keysym))
+}
+
+/// Keysym по имени клавиши: печатный символ или имя вроде `Return`, `F5`.
+fn keysym_from_name(name: &str) -> Option<Keysym> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return matches!(c, ' '..='~' | '\u{a0}'..='\u{ff}').then(|| lowercase(key(c)));
+    }
+    let lower = name.to_lowercase();
+    if let Some(n) = lower
+        .strip_prefix('f')
+        .and_then(|n| n.parse::<Keysym>().ok())
+    {
+        return (1..=24).contains(&n).then(|| 0xffbd + n);
+    }
+    Some(match lower.as_str() {
+        "space" => key(' '),
+        "return" | "enter" => XK_RETURN,
+        "tab" => XK_TAB,
+        "escape" | "esc" => XK_ESCAPE,
+        "backspace" => 0xff08,
+        "delete" => 0xffff,
+        "insert" => 0xff63,
+        "home" => 0xff50,
+        "end" => 0xff57,
+        "prior" | "page_up" => 0xff55,
+        "next" | "page_down" => 0xff56,
+        "print" => 0xff61,
+        "left" => XK_LEFT,
+        "up" => XK_UP,
+        "right" => XK_RIGHT,
+        "down" => XK_DOWN,
+        "plus" => key('+'),
+        "minus" => key('-'),
+        "equal" => key('='),
+        "comma" => key(','),
+        "period" => key('.'),
+        "slash" => key('/'),
+        "backslash" => key('\\'),
+        "semicolon" => key(';'),
+        "apostrophe" => key('\''),
+        "grave" => key('`'),
+        "bracketleft" => key('['),
+        "bracketright" => key(']'),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_combo_reads_modifiers_and_key() {
+        assert_eq!(
+            parse_combo("Mod+Shift+q", ModMask::M4),
+            Ok((ModMask::M4 | ModMask::SHIFT, key('q')))
+        );
+        assert_eq!(
+            parse_combo("ctrl + alt + Return", ModMask::M4),
+            Ok((ModMask::CONTROL | ModMask::M1, XK_RETURN))
+        );
+        assert_eq!(
+            parse_combo("Mod+Q", ModMask::M1),
+            Ok((ModMask::M1, key('q')))
+        );
+        assert_eq!(parse_combo("F5", ModMask::M4), Ok((0u16.into(), 0xffc2)));
+        assert_eq!(
+            parse_combo("Mod+plus", ModMask::M4),
+            Ok((ModMask::M4, key('+')))
+        );
+    }
+
+    #[test]
+    fn parse_combo_errors() {
+        let error = |combo| parse_combo(combo, ModMask::M4).unwrap_err();
+        assert_eq!(error(""), "не указана клавиша");
+        assert_eq!(error("Mod+"), "не указана клавиша");
+        assert_eq!(error("Hyper+q"), "неизвестный модификатор Hyper");
+        assert_eq!(error("Mod+Foo"), "неизвестная клавиша Foo");
+        assert_eq!(error("Mod+F25"), "неизвестная клавиша F25");
+    }
+}
@@ -0,0 +1,146 @@
+use super::tile::master_stack;
+use super::{Geometry, Layout, LayoutParams, Rect, split_evenly};
+use crate::client::Client;
+
+pub struct ThreeColumn;
+
+impl Layout for ThreeColumn {
+    fn arrange(&self, area: Rect, clients: &[&Client], params: &LayoutParams) -> Vec<Geometry> {
+        three_column(area, clients.len(), params.master_factor)
+            .into_iter()
+            .map(Some)
+            .collect()
+    }
+}
+
+pub struct CenteredMaster;
+
+impl Layout for CenteredMaster {
+    fn arrange(&self, area: Rect, clients: &[&Client], params: &LayoutParams) -> Vec<Geometry> {
+        centered_master(area, clients.len(), params.master_factor)
+            .into_iter()
+            .map(Some)
+            .collect()
+    }
+}
+
+/// Колонки по бокам от мастера шириной `master_factor` и сам мастер.
+fn columns(area: Rect, master_factor: f32) -> (Rect, Rect, Rect) {
+    let master_width = ((area.width as f32 * master_factor) as u32).min(area.width);
+    let side = area.width.saturating_sub(master_width) / 2;
+    let left = Rect {
+        width: side,
+        ..area
+    };
+    let master = Rect {
+        x: area.x + side as i32,
+        width: master_width,
+        ..area
+    };
+    let right = Rect {
+        x: master.x + master_width as i32,
+        width: area.width.saturating_sub(side + master_width),
+        ..area
+    };
+    (left, master, right)
+}
+
+/// Три колонки: стопка – мастер – стопка. Мастер занимает долю
+/// `master_factor` ширины, первая половина стопки уходит влево,
+/// вторая — вправо. С одним окном в стопке раскладка вырождается
+/// в обычную двухколоночную.
+pub fn three_column(area: Rect, count: usize, master_factor: f32) -> Vec<Rect> {
+    if count < 3 {
+        return master_stack(area, count, true, master_factor, 1);
+    }
+
+    let (left, master, right) = columns(area, master_factor);
+    let stack = count - 1;
+    let on_left = stack / 2;
+    let mut geometries = vec![master];
+    geometries.extend(split_evenly(left, on_left, false));
+    geometries.extend(split_evenly(right, stack - on_left, false));
+    geometries
+}
+
+/// Мастер по центру шириной `master_factor` даже при одном окне в
+/// стопке; окна стопки чередуются: чётные — справа, нечётные — слева,
+/// так что боковые колонки заполняются симметрично.
+pub fn centered_master(area: Rect, count: usize, master_factor: f32) -> Vec<Rect> {
+    if count < 2 {
+        return master_stack(area, count, true, master_factor, 1);
+    }
+
+    let (left, master, right) = columns(area, master_factor);
+    let stack = count - 1;
+    let on_left = stack / 2;
+    let mut lefts = split_evenly(left, on_left, false).into_iter();
+    let mut rights = split_evenly(right, stack - on_left, false).into_iter();
+
+    let mut geometries = vec![master];
+    for i in 0..stack {
+        let side = if i % 2 == 0 { &mut rights } else { &mut lefts };
+        geometries.extend(side.next());
+    }
+    geometries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::fixture::{AREA, rect};
+
+    #[test]
+    fn three_column_small_counts() {
+        assert!(three_column(AREA, 0, 0.5).is_empty());
+        assert_eq!(three_column(AREA, 1, 0.5), [AREA]);
+        assert_eq!(
+            three_column(AREA, 2, 0.5),
+            [rect(0, 0, 50, 60), rect(50, 0, 50, 60)]
+        );
+    }
+
+    #[test]
+    fn three_column_splits_stack_between_sides() {
+        assert_eq!(
+            three_column(AREA, 4, 0.5),
+            [
+                rect(25, 0, 50, 60),
+                rect(0, 0, 25, 60),
+                rect(75, 0, 25, 30),
+                rect(75, 30, 25, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn centered_master_small_counts() {
+        assert!(centered_master(AREA, 0, 0.5).is_empty());
+        assert_eq!(centered_master(AREA, 1, 0.5), [AREA]);
+        // Мастер остаётся посередине и со стопкой из одного окна.
+        assert_eq!(
+            centered_master(AREA, 2, 0.5),
+            [rect(25, 0, 50, 60), rect(75, 0, 25, 60)]
+        );
+    }
+
+    #[test]
+    fn centered_master_alternates_sides() {
+        assert_eq!(
+            centered_master(AREA, 4, 0.5),
+            [
+                rect(25, 0, 50, 60),
+                rect(75, 0, 25, 30),
+                rect(0, 0, 25, 60),
+                rect(75, 30, 25, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn columns_stay_inside_area() {
+        let rects = centered_master(AREA, 3, 1.5);
+        assert_eq!(rects[0], AREA);
+        assert!(rects[1..].iter().all(|r| r.width == 0));
+    }
+}
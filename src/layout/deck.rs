@@ -0,0 +1,62 @@
+use super::tile::master_stack;
+use super::{Geometry, Layout, LayoutParams, Rect};
+use crate::client::Client;
+
+pub struct Deck;
+
+impl Layout for Deck {
+    fn arrange(&self, area: Rect, clients: &[&Client], params: &LayoutParams) -> Vec<Geometry> {
+        deck(
+            area,
+            clients.len(),
+            params.deck_shown,
+            params.master_factor,
+            params.nmaster,
+        )
+    }
+}
+
+/// Колода: как master–stack, но из стопки видно лишь окно с номером
+/// `shown`; остальные окна стопки получают `None` и скрываются.
+pub fn deck(
+    area: Rect,
+    count: usize,
+    shown: usize,
+    master_factor: f32,
+    nmaster: usize,
+) -> Vec<Option<Rect>> {
+    let masters = nmaster.min(count);
+    let visible = master_stack(area, count.min(masters + 1), true, master_factor, nmaster);
+    (0..count)
+        .map(|i| match i {
+            i if i < masters => visible.get(i).copied(),
+            i if i - masters == shown % (count - masters) => visible.get(masters).copied(),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::fixture::{AREA, rect};
+
+    #[test]
+    fn deck_small_counts() {
+        assert!(deck(AREA, 0, 0, 0.5, 1).is_empty());
+        assert_eq!(deck(AREA, 1, 0, 0.5, 1), [Some(AREA)]);
+        assert_eq!(
+            deck(AREA, 2, 0, 0.5, 1),
+            [Some(rect(0, 0, 50, 60)), Some(rect(50, 0, 50, 60))]
+        );
+    }
+
+    #[test]
+    fn deck_shows_one_stack_window() {
+        let (master, stack) = (Some(rect(0, 0, 50, 60)), Some(rect(50, 0, 50, 60)));
+        assert_eq!(deck(AREA, 4, 1, 0.5, 1), [master, None, stack, None]);
+        // Номер показанного окна идёт по кругу.
+        assert_eq!(deck(AREA, 4, 4, 0.5, 1), [master, None, stack, None]);
+        assert_eq!(deck(AREA, 2, 1, 0.5, 0), [None, Some(AREA)]);
+    }
+}
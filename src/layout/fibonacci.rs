@@ -0,0 +1,69 @@
+use super::{Geometry, Layout, LayoutParams, Rect, halve};
+use crate::client::Client;
+
+pub struct Fibonacci {
+    pub spiral: bool,
+}
+
+impl Layout for Fibonacci {
+    fn arrange(&self, area: Rect, clients: &[&Client], _: &LayoutParams) -> Vec<Geometry> {
+        fibonacci(area, clients.len(), self.spiral)
+            .into_iter()
+            .map(Some)
+            .collect()
+    }
+}
+
+/// Раскладки Фибоначчи: каждое следующее окно занимает половину
+/// оставшегося места, оси деления чередуются. В режиме `spiral`
+/// окна закручиваются по часовой стрелке, иначе (dwindle) всё время
+/// уходят вправо-вниз.
+pub fn fibonacci(area: Rect, count: usize, spiral: bool) -> Vec<Rect> {
+    let mut geometries = Vec::with_capacity(count);
+    let mut rest = area;
+    for i in 0..count {
+        if i == count - 1 {
+            geometries.push(rest);
+            break;
+        }
+        let (first, second) = halve(rest, i % 2 == 0);
+        // В спирали на третьем и четвёртом шаге окно берёт дальнюю половину.
+        if spiral && i % 4 >= 2 {
+            geometries.push(second);
+            rest = first;
+        } else {
+            geometries.push(first);
+            rest = second;
+        }
+    }
+    geometries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::fixture::{AREA, rect};
+
+    #[test]
+    fn fibonacci_small_counts() {
+        for spiral in [false, true] {
+            assert!(fibonacci(AREA, 0, spiral).is_empty());
+            assert_eq!(fibonacci(AREA, 1, spiral), [AREA]);
+            assert_eq!(
+                fibonacci(AREA, 2, spiral),
+                [rect(0, 0, 50, 60), rect(50, 0, 50, 60)]
+            );
+        }
+    }
+
+    #[test]
+    fn spiral_turns_back_where_dwindle_goes_on() {
+        let head = [rect(0, 0, 50, 60), rect(50, 0, 50, 30)];
+        let dwindle = fibonacci(AREA, 4, false);
+        assert_eq!(dwindle[..2], head);
+        assert_eq!(dwindle[2..], [rect(50, 30, 25, 30), rect(75, 30, 25, 30)]);
+        let spiral = fibonacci(AREA, 4, true);
+        assert_eq!(spiral[..2], head);
+        assert_eq!(spiral[2..], [rect(75, 30, 25, 30), rect(50, 30, 25, 30)]);
+    }
+}
@@ -0,0 +1,54 @@
+use super::{Geometry, Layout, LayoutParams, Rect, split_evenly};
+use crate::client::Client;
+
+pub struct Grid;
+
+impl Layout for Grid {
+    fn arrange(&self, area: Rect, clients: &[&Client], _: &LayoutParams) -> Vec<Geometry> {
+        grid(area, clients.len()).into_iter().map(Some).collect()
+    }
+}
+
+/// Сетка из N×M ячеек: строки заполняются слева направо,
+/// окна неполной последней строки растягиваются на всю её ширину.
+pub fn grid(area: Rect, count: usize) -> Vec<Rect> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let cols = (1..=count).find(|c| c * c >= count).unwrap_or(count);
+    let rows = count.div_ceil(cols);
+
+    split_evenly(area, rows, false)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(row, row_area)| {
+            let in_row = cols.min(count - row * cols);
+            split_evenly(row_area, in_row, true)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::fixture::{AREA, rect};
+
+    #[test]
+    fn grid_small_counts() {
+        assert!(grid(AREA, 0).is_empty());
+        assert_eq!(grid(AREA, 1), [AREA]);
+        assert_eq!(grid(AREA, 2), [rect(0, 0, 50, 60), rect(50, 0, 50, 60)]);
+    }
+
+    #[test]
+    fn grid_stretches_incomplete_last_row() {
+        assert_eq!(
+            grid(AREA, 3),
+            [
+                rect(0, 0, 50, 30),
+                rect(50, 0, 50, 30),
+                rect(0, 30, 100, 30)
+            ]
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use std::ops::RangeInclusive;
+
+use serde::Deserialize;
+
+use crate::client::Client;
+
+pub use columns::{CenteredMaster, ThreeColumn};
+pub use deck::Deck;
+pub use fibonacci::Fibonacci;
+pub use grid::{Grid, grid};
+pub use monocle::Monocle;
+pub use tile::Tile;
+
+mod columns;
+mod deck;
+mod fibonacci;
+mod grid;
+mod monocle;
+mod tile;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutMode {
+    Horizontal,
+    Vertical,
+    /// Сфокусированное окно на весь экран, остальные скрыты под ним.
+    Monocle,
+    Grid,
+    Spiral,
+    Dwindle,
+    /// Мастер слева, из стопки справа видно только одно окно.
+    Deck,
+    /// Мастер посередине, стопка делится на левую и правую колонки.
+    ThreeColumn,
+    /// Мастер всегда по центру, окна стопки поочерёдно справа и слева.
+    CenteredMaster,
+    /// Без тайлинга: окна сами выбирают геометрию через ConfigureRequest.
+    Floating,
+    /// Каждое новое окно делит пополам область сфокусированного.
+    Bsp,
+    /// Ручное управление деревом, как в i3: новое окно встаёт рядом
+    /// со сфокусированным, направление задаётся командами split.
+    Tree,
+}
+
+impl LayoutMode {
+    /// Списочная раскладка для режима. Для плавающего режима и режимов
+    /// с деревом разбиений её нет: геометрию там задают окна или дерево.
+    pub fn layout(self) -> Option<&'static dyn Layout> {
+        Some(match self {
+            LayoutMode::Horizontal => &Tile { horizontal: true },
+            LayoutMode::Vertical => &Tile { horizontal: false },
+            LayoutMode::Monocle => &Monocle,
+            LayoutMode::Grid => &Grid,
+            LayoutMode::Spiral => &Fibonacci { spiral: true },
+            LayoutMode::Dwindle => &Fibonacci { spiral: false },
+            LayoutMode::Deck => &Deck,
+            LayoutMode::ThreeColumn => &ThreeColumn,
+            LayoutMode::CenteredMaster => &CenteredMaster,
+            LayoutMode::Floating | LayoutMode::Bsp | LayoutMode::Tree => return None,
+        })
+    }
+}
+
+/// Пределы доли мастер-области: за ними одна из областей вырождается.
+pub const MASTER_FACTOR_RANGE: RangeInclusive<f32> = 0.1..=0.9;
+
+/// Настраиваемые командами параметры раскладок.
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutParams {
+    pub master_factor: f32,
+    pub nmaster: usize,
+    /// Какое окно стопки показывает колода.
+    pub deck_shown: usize,
+}
+
+/// Место окна в раскладке; `None` — окно скрыто.
+pub type Geometry = Option<Rect>;
+
+/// Раскладка, расставляющая окна по порядку их следования в списке.
+/// Новая раскладка — это отдельный модуль с реализацией трейта и
+/// строка в `LayoutMode::layout`; `arrange_windows` её не касается.
+pub trait Layout {
+    /// Возвращает по одной геометрии на каждое окно из `clients`.
+    fn arrange(&self, area: Rect, clients: &[&Client], params: &LayoutParams) -> Vec<Geometry>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Down,
+    Up,
+    Right,
+}
+
+impl Direction {
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Direction::Left | Direction::Right)
+    }
+
+    /// Лежит ли сторона в начале оси (слева или сверху).
+    pub fn is_leading(self) -> bool {
+        matches!(self, Direction::Left | Direction::Up)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn center(self) -> (i32, i32) {
+        (
+            self.x + self.width as i32 / 2,
+            self.y + self.height as i32 / 2,
+        )
+    }
+
+    /// Прямоугольник того же размера, насколько позволяет `area`,
+    /// по центру `area`.
+    pub fn centered_in(self, area: Rect) -> Rect {
+        let width = self.width.min(area.width);
+        let height = self.height.min(area.height);
+        Rect {
+            x: area.x + ((area.width - width) / 2) as i32,
+            y: area.y + ((area.height - height) / 2) as i32,
+            width,
+            height,
+        }
+    }
+
+    /// Прямоугольник, отступивший от каждого края на `margin`; стороны
+    /// не меньше 1.
+    pub fn shrink(self, margin: u32) -> Rect {
+        Rect {
+            x: self.x + margin as i32,
+            y: self.y + margin as i32,
+            width: self.width.saturating_sub(2 * margin).max(1),
+            height: self.height.saturating_sub(2 * margin).max(1),
+        }
+    }
+
+    /// Площадь пересечения с `other`.
+    pub fn overlap(self, other: Rect) -> u64 {
+        let width =
+            (self.x + self.width as i32).min(other.x + other.width as i32) - self.x.max(other.x);
+        let height =
+            (self.y + self.height as i32).min(other.y + other.height as i32) - self.y.max(other.y);
+        width.max(0) as u64 * height.max(0) as u64
+    }
+}
+
+/// Преобразование готовой раскладки: поворот на 90° (транспонирование,
+/// мастер слева становится мастером сверху) и зеркалирование по
+/// горизонтали. Позволяет одним кодом раскладок обслуживать и
+/// горизонтальные, и вертикальные мониторы.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Transform {
+    pub rotated: bool,
+    pub mirrored: bool,
+}
+
+impl Transform {
+    /// Область, в которой раскладку нужно посчитать до преобразования.
+    pub fn source_area(self, area: Rect) -> Rect {
+        if self.rotated {
+            Rect {
+                width: area.height,
+                height: area.width,
+                ..area
+            }
+        } else {
+            area
+        }
+    }
+
+    /// Переносит прямоугольник из `source_area(area)` в `area`.
+    pub fn apply(self, area: Rect, rect: Rect) -> Rect {
+        let mut out = if self.rotated {
+            Rect {
+                x: area.x + (rect.y - area.y),
+                y: area.y + (rect.x - area.x),
+                width: rect.height,
+                height: rect.width,
+            }
+        } else {
+            rect
+        };
+        if self.mirrored {
+            out.x = area.x + area.width as i32 - (out.x - area.x) - out.width as i32;
+        }
+        out
+    }
+}
+
+/// Ближайший к `from` прямоугольник, центр которого лежит в направлении
+/// `direction`. Смещение поперёк направления штрафуется вдвое, чтобы
+/// предпочитать соседей на одной линии.
+pub fn nearest<T>(
+    from: Rect,
+    candidates: impl IntoIterator<Item = (T, Rect)>,
+    direction: Direction,
+) -> Option<T> {
+    let (x, y) = from.center();
+    candidates
+        .into_iter()
+        .filter_map(|(item, rect)| {
+            let (cx, cy) = rect.center();
+            let (dx, dy) = (cx - x, cy - y);
+            let (ahead, across) = match direction {
+                Direction::Left => (-dx, dy),
+                Direction::Right => (dx, dy),
+                Direction::Up => (-dy, dx),
+                Direction::Down => (dy, dx),
+            };
+            (ahead > 0).then_some((item, ahead + 2 * across.abs()))
+        })
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(item, _)| item)
+}
+
+/// Сдвиг отрезка `[start, end)` вдоль оси, прижимающий его ближний или
+/// дальний конец к ближайшему из `edges` не дальше `threshold`.
+pub fn snap(start: i32, end: i32, edges: &[i32], threshold: u32) -> i32 {
+    edges
+        .iter()
+        .flat_map(|&edge| [edge - start, edge - end])
+        .filter(|delta| delta.unsigned_abs() <= threshold)
+        .min_by_key(|delta| delta.abs())
+        .unwrap_or(0)
+}
+
+/// Делит область на `count` частей на равные доли вдоль одной оси.
+pub fn split_evenly(area: Rect, count: usize, horizontal: bool) -> Vec<Rect> {
+    split_weighted(area, &vec![1.0; count], horizontal)
+}
+
+/// Делит область вдоль одной оси пропорционально `weights`.
+/// Остаток от округления отдаётся последней части, чтобы не оставалось щелей.
+pub fn split_weighted(area: Rect, weights: &[f32], horizontal: bool) -> Vec<Rect> {
+    let total = if horizontal { area.width } else { area.height };
+    let sum: f32 = weights.iter().sum();
+    let mut offset = 0;
+    weights
+        .iter()
+        .enumerate()
+        .map(|(i, weight)| {
+            let size = if i == weights.len() - 1 {
+                total - offset
+            } else {
+                ((total as f32 * weight / sum) as u32).min(total - offset)
+            };
+            let start = offset;
+            offset += size;
+            if horizontal {
+                Rect {
+                    x: area.x + start as i32,
+                    width: size,
+                    ..area
+                }
+            } else {
+                Rect {
+                    y: area.y + start as i32,
+                    height: size,
+                    ..area
+                }
+            }
+        })
+        .collect()
+}
+
+/// Делит область пополам вдоль выбранной оси: (первая половина, вторая).
+pub fn halve(area: Rect, horizontal: bool) -> (Rect, Rect) {
+    let mut parts = split_evenly(area, 2, horizontal).into_iter();
+    (parts.next().unwrap_or(area), parts.next().unwrap_or(area))
+}
+
+/// Область и прямоугольники для тестов раскладок.
+#[cfg(test)]
+pub mod fixture {
+    use super::Rect;
+
+    pub const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 100,
+        height: 60,
+    };
+
+    pub fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixture::{AREA, rect};
+    use super::*;
+
+    #[test]
+    fn transform_rotates_and_mirrors() {
+        let rotated = Transform {
+            rotated: true,
+            mirrored: false,
+        };
+        let source = rotated.source_area(AREA);
+        assert_eq!((source.width, source.height), (60, 100));
+        // Стопка справа в повёрнутой области оказывается снизу.
+        assert_eq!(
+            rotated.apply(AREA, rect(30, 0, 30, 100)),
+            rect(0, 30, 100, 30)
+        );
+
+        let mirrored = Transform {
+            rotated: false,
+            mirrored: true,
+        };
+        assert_eq!(
+            mirrored.apply(AREA, rect(0, 0, 30, 60)),
+            rect(70, 0, 30, 60)
+        );
+    }
+
+    #[test]
+    fn split_weighted_follows_weights() {
+        assert_eq!(
+            split_weighted(AREA, &[1.0, 3.0], true),
+            [rect(0, 0, 25, 60), rect(25, 0, 75, 60)]
+        );
+        assert_eq!(
+            split_weighted(AREA, &[2.0, 1.0], false),
+            [rect(0, 0, 100, 40), rect(0, 40, 100, 20)]
+        );
+        assert!(split_weighted(AREA, &[], true).is_empty());
+    }
+
+    #[test]
+    fn split_weighted_gives_remainder_to_last_part() {
+        let widths: Vec<u32> = split_weighted(AREA, &[1.0, 1.0, 1.0], true)
+            .iter()
+            .map(|r| r.width)
+            .collect();
+        assert_eq!(widths, [33, 33, 34]);
+    }
+
+    #[test]
+    fn shrink_keeps_sides_positive() {
+        assert_eq!(AREA.shrink(5), rect(5, 5, 90, 50));
+        assert_eq!(rect(10, 20, 4, 4).shrink(5), rect(15, 25, 1, 1));
+    }
+}
@@ -0,0 +1,11 @@
+use super::{Geometry, Layout, LayoutParams, Rect};
+use crate::client::Client;
+
+/// Сфокусированное окно на весь экран, остальные скрыты под ним.
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn arrange(&self, area: Rect, clients: &[&Client], _: &LayoutParams) -> Vec<Geometry> {
+        vec![Some(area); clients.len()]
+    }
+}
@@ -0,0 +1,119 @@
+use super::{Geometry, Layout, LayoutParams, Rect, split_evenly};
+use crate::client::Client;
+
+/// master–stack в духе tile у dwm.
+pub struct Tile {
+    pub horizontal: bool,
+}
+
+impl Layout for Tile {
+    fn arrange(&self, area: Rect, clients: &[&Client], params: &LayoutParams) -> Vec<Geometry> {
+        master_stack(
+            area,
+            clients.len(),
+            self.horizontal,
+            params.master_factor,
+            params.nmaster,
+        )
+        .into_iter()
+        .map(Some)
+        .collect()
+    }
+}
+
+/// Раскладка master–stack: первые `nmaster` окон делят долю
+/// `master_factor` экрана, остальные — оставшееся место.
+/// При `horizontal` мастер-область слева, иначе — сверху.
+/// Если одна из областей пуста, вторая занимает весь экран.
+pub fn master_stack(
+    area: Rect,
+    count: usize,
+    horizontal: bool,
+    master_factor: f32,
+    nmaster: usize,
+) -> Vec<Rect> {
+    let masters = nmaster.min(count);
+    if masters == 0 || masters == count {
+        return split_evenly(area, count, !horizontal);
+    }
+
+    let (master, stack) = if horizontal {
+        let master = ((area.width as f32 * master_factor) as u32).min(area.width);
+        (
+            Rect {
+                width: master,
+                ..area
+            },
+            Rect {
+                x: area.x + master as i32,
+                width: area.width.saturating_sub(master),
+                ..area
+            },
+        )
+    } else {
+        let master = ((area.height as f32 * master_factor) as u32).min(area.height);
+        (
+            Rect {
+                height: master,
+                ..area
+            },
+            Rect {
+                y: area.y + master as i32,
+                height: area.height.saturating_sub(master),
+                ..area
+            },
+        )
+    };
+
+    let mut geometries = split_evenly(master, masters, !horizontal);
+    geometries.extend(split_evenly(stack, count - masters, !horizontal));
+    geometries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::fixture::{AREA, rect};
+
+    #[test]
+    fn master_stack_small_counts() {
+        assert!(master_stack(AREA, 0, true, 0.5, 1).is_empty());
+        assert_eq!(master_stack(AREA, 1, true, 0.5, 1), [AREA]);
+        assert_eq!(
+            master_stack(AREA, 2, true, 0.5, 1),
+            [rect(0, 0, 50, 60), rect(50, 0, 50, 60)]
+        );
+        assert_eq!(
+            master_stack(AREA, 2, false, 0.5, 1),
+            [rect(0, 0, 100, 30), rect(0, 30, 100, 30)]
+        );
+    }
+
+    #[test]
+    fn master_stack_follows_master_factor() {
+        assert_eq!(
+            master_stack(AREA, 3, true, 0.6, 1),
+            [
+                rect(0, 0, 60, 60),
+                rect(60, 0, 40, 30),
+                rect(60, 30, 40, 30)
+            ]
+        );
+    }
+
+    #[test]
+    fn master_stack_without_one_of_the_areas() {
+        // Без мастеров и без стопки окна делят весь экран поперёк оси.
+        let even = [rect(0, 0, 100, 30), rect(0, 30, 100, 30)];
+        assert_eq!(master_stack(AREA, 2, true, 0.5, 0), even);
+        assert_eq!(master_stack(AREA, 2, true, 0.5, 2), even);
+        assert_eq!(master_stack(AREA, 2, true, 0.5, 5), even);
+    }
+
+    #[test]
+    fn master_stack_stays_inside_area() {
+        let rects = master_stack(AREA, 2, true, 1.5, 1);
+        assert_eq!(rects[0], AREA);
+        assert_eq!(rects[1].width, 0);
+    }
+}
@@ -1,161 +1,4187 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
 use x11rb::connection::Connection;
-use x11rb::errors::ConnectionError;
+use x11rb::errors::{ReplyError, ReplyOrIdError};
+use x11rb::properties::{WmClass, WmHints, WmSizeHints};
 use x11rb::protocol::xproto::*;
-use x11rb::protocol::Event;
+use x11rb::protocol::{ErrorKind, Event};
 
-#[derive(Debug, Clone, Copy)]
-enum LayoutMode {
-    Horizontal,
-    Vertical,
+use bar::{BarContent, BarWorkspace, Bars};
+use client::Client;
+use config::{Config, NewWindowFocus};
+use ewmh::Atoms;
+use icccm::WmState;
+use keys::{
+    Keymap, XK_ALT_L, XK_ALT_R, XK_DOWN, XK_ESCAPE, XK_LEFT, XK_RETURN, XK_RIGHT, XK_TAB, XK_UP,
+    key,
+};
+use layout::{Direction, LayoutMode, MASTER_FACTOR_RANGE, Rect, halve, nearest, snap};
+use monitor::{Monitor, MonitorTarget, Struts};
+use overview::Overview;
+use rules::{Properties, WindowType};
+use state::FloatingMemory;
+use tabbar::{TAB_BAR_HEIGHT, TabBars};
+use tree::{Arrangement, ContainerLayout, TabBar};
+use workspace::Workspace;
+
+mod bar;
+mod client;
+mod config;
+mod ewmh;
+mod icccm;
+mod ipc;
+mod keys;
+mod layout;
+mod monitor;
+mod overview;
+mod randr;
+mod rules;
+mod state;
+mod tabbar;
+mod tree;
+mod workspace;
+mod xinerama;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Action {
+    ToggleLayout,
+    TogglePseudoTiled,
+    /// Закрепить окно на всех столах или открепить.
+    ToggleSticky,
+    /// Вынуть окно из тайлинга или вернуть обратно.
+    ToggleFloating,
+    /// Держать окно над остальными или перестать.
+    ToggleAbove,
+    /// Развернуть окно на рабочую область монитора по осям (по горизонтали,
+    /// по вертикали) или вернуть прежнюю геометрию.
+    ToggleMaximize(bool, bool),
+    /// Развернуть окно на весь монитор без рамки или вернуть обратно.
+    ToggleFullscreen,
+    /// Поставить плавающее окно по центру монитора.
+    CenterFloating,
+    /// Попросить окно с фокусом закрыться через WM_DELETE_WINDOW.
+    CloseWindow,
+    /// Сдвинуть плавающее окно на шаг.
+    MoveFloating(Direction),
+    /// Сдвинуть правый или нижний край плавающего окна на шаг: вправо
+    /// и вниз окно растёт, влево и вверх — сжимается.
+    ResizeFloating(Direction),
+    /// Вызвать блокнот с этим именем или спрятать его.
+    ToggleScratchpad(String),
+    GrowMaster,
+    ShrinkMaster,
+    Mirror,
+    Rotate,
+    Balance,
+    IncMasterCount,
+    DecMasterCount,
+    FocusNext,
+    FocusPrev,
+    /// Перейти к окну, последним попросившему внимания.
+    FocusUrgent,
+    /// Вернуться к предыдущему окну с фокусом, на каком бы столе оно ни было.
+    FocusLast,
+    /// Листать окна в порядке последнего фокуса; `true` — к давним.
+    FocusMru(bool),
+    /// Переключатель Alt-Tab: листает, пока зажат Alt.
+    AltTab(bool),
+    /// Перевести фокус на ближайшее окно в направлении.
+    FocusDirection(Direction),
+    SplitHorizontal,
+    SplitVertical,
+    Preselect(Direction),
+    CancelPreselect,
+    ToggleTabbed,
+    ToggleStacked,
+    NextTab,
+    PrevTab,
+    ViewWorkspace(usize),
+    /// Добавить стол к видимым или убрать его из вида.
+    ToggleView(usize),
+    /// Показать окна всех столов сразу.
+    ViewAll,
+    /// Переименовать основной видимый стол.
+    RenameWorkspace(String),
+    /// Добавить сфокусированному окну тег стола или снять его.
+    ToggleTag(usize),
+    /// Перейти на первый пустой стол.
+    EmptyWorkspace,
+    /// Перенести сфокусированное окно на первый пустой стол; `true` —
+    /// перейти следом.
+    MoveToEmptyWorkspace(bool),
+    /// Вернуться на предыдущий просмотренный стол.
+    PreviousWorkspace,
+    /// Перенести сфокусированное окно на стол; `true` — перейти следом.
+    MoveToWorkspace(usize, bool),
+    /// Перевести фокус на другой монитор.
+    FocusMonitor(MonitorTarget),
+    /// Перенести сфокусированное окно на другой монитор.
+    MoveToMonitor(MonitorTarget),
+    /// Открыть обзор столов или закрыть его.
+    Overview,
+    /// Применить профиль мониторов с этим именем.
+    SwitchProfile(String),
+    /// Ответить на IPC-запрос.
+    Query(ipc::Query),
+    /// Перечитать файл настроек.
+    Reload,
+    /// Запустить команду оболочки.
+    Spawn(String),
+    /// Выполнить команду `rewm msg` из привязки в настройках. Разбирается
+    /// при срабатывании, чтобы стол искался на текущем мониторе.
+    Command(String),
+    Quit,
+}
+
+struct KeyBinding {
+    modifiers: ModMask,
+    keysym: Keysym,
+    action: Action,
+}
+
+fn ignored_modifiers() -> [ModMask; 4] {
+    // Lock и NumLock (Mod2) не должны влиять на срабатывание привязок.
+    [
+        ModMask::from(0u16),
+        ModMask::LOCK,
+        ModMask::M2,
+        ModMask::LOCK | ModMask::M2,
+    ]
+}
+
+/// Привязки по умолчанию, поверх которых ложатся привязки из настроек.
+fn key_bindings(config: &Config) -> Vec<KeyBinding> {
+    let modkey = config.modifier.mask();
+    let mut bindings = vec![
+        // Mod4+Space
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key(' '),
+            action: Action::ToggleLayout,
+        },
+        // Mod4+Shift+P
+        KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: key('p'),
+            action: Action::TogglePseudoTiled,
+        },
+        // Mod4+=
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('='),
+            action: Action::GrowMaster,
+        },
+        // Mod4+-
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('-'),
+            action: Action::ShrinkMaster,
+        },
+        // Mod4+M
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('m'),
+            action: Action::Mirror,
+        },
+        // Mod4+R
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('r'),
+            action: Action::Rotate,
+        },
+        // Mod4+Shift+=
+        KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: key('='),
+            action: Action::Balance,
+        },
+        // Mod4+I
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('i'),
+            action: Action::IncMasterCount,
+        },
+        // Mod4+D
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('d'),
+            action: Action::DecMasterCount,
+        },
+        // Mod4+Tab
+        KeyBinding {
+            modifiers: modkey,
+            keysym: XK_TAB,
+            action: Action::FocusMru(true),
+        },
+        // Mod4+Shift+Tab
+        KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: XK_TAB,
+            action: Action::FocusMru(false),
+        },
+        // Mod4+U
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('u'),
+            action: Action::FocusUrgent,
+        },
+        // Mod4+H/J/K/L
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('h'),
+            action: Action::FocusDirection(Direction::Left),
+        },
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('j'),
+            action: Action::FocusDirection(Direction::Down),
+        },
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('k'),
+            action: Action::FocusDirection(Direction::Up),
+        },
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('l'),
+            action: Action::FocusDirection(Direction::Right),
+        },
+        // Mod4+B
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('b'),
+            action: Action::SplitHorizontal,
+        },
+        // Mod4+V
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('v'),
+            action: Action::SplitVertical,
+        },
+        // Mod4+Ctrl+H/J/K/L
+        KeyBinding {
+            modifiers: modkey | ModMask::CONTROL,
+            keysym: key('h'),
+            action: Action::Preselect(Direction::Left),
+        },
+        KeyBinding {
+            modifiers: modkey | ModMask::CONTROL,
+            keysym: key('j'),
+            action: Action::Preselect(Direction::Down),
+        },
+        KeyBinding {
+            modifiers: modkey | ModMask::CONTROL,
+            keysym: key('k'),
+            action: Action::Preselect(Direction::Up),
+        },
+        KeyBinding {
+            modifiers: modkey | ModMask::CONTROL,
+            keysym: key('l'),
+            action: Action::Preselect(Direction::Right),
+        },
+        // Mod4+Ctrl+Space
+        KeyBinding {
+            modifiers: modkey | ModMask::CONTROL,
+            keysym: key(' '),
+            action: Action::CancelPreselect,
+        },
+        // Mod4+Shift+S
+        KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: key('s'),
+            action: Action::ToggleSticky,
+        },
+        // Mod4+A
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('a'),
+            action: Action::ToggleAbove,
+        },
+        // Mod4+X, Mod4+Shift+X, Mod4+Ctrl+X
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('x'),
+            action: Action::ToggleMaximize(true, true),
+        },
+        KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: key('x'),
+            action: Action::ToggleMaximize(false, true),
+        },
+        KeyBinding {
+            modifiers: modkey | ModMask::CONTROL,
+            keysym: key('x'),
+            action: Action::ToggleMaximize(true, false),
+        },
+        // Mod4+F
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('f'),
+            action: Action::ToggleFullscreen,
+        },
+        // Mod4+C
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('c'),
+            action: Action::CenterFloating,
+        },
+        // Mod4+Shift+Space
+        KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: key(' '),
+            action: Action::ToggleFloating,
+        },
+        // Mod4+W
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('w'),
+            action: Action::ToggleTabbed,
+        },
+        // Mod4+S
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('s'),
+            action: Action::ToggleStacked,
+        },
+        // Mod4+]
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key(']'),
+            action: Action::NextTab,
+        },
+        // Mod4+[
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('['),
+            action: Action::PrevTab,
+        },
+        // Mod4+O
+        KeyBinding {
+            modifiers: modkey,
+            keysym: key('o'),
+            action: Action::Overview,
+        },
+        // Mod4+Shift+Q
+        KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: key('q'),
+            action: Action::CloseWindow,
+        },
+        // Mod4+Ctrl+Q
+        KeyBinding {
+            modifiers: ModMask::CONTROL | modkey,
+            keysym: key('q'),
+            action: Action::Quit,
+        },
+        // Mod4+Shift+R
+        KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: key('r'),
+            action: Action::Reload,
+        },
+    ];
+    // Mod4+`, Mod4+Shift+`
+    bindings.push(KeyBinding {
+        modifiers: modkey,
+        keysym: key('`'),
+        action: Action::PreviousWorkspace,
+    });
+    bindings.push(KeyBinding {
+        modifiers: modkey | ModMask::SHIFT,
+        keysym: key('`'),
+        action: Action::FocusLast,
+    });
+    // Mod4+E, Mod4+Shift+E, Mod4+Ctrl+Shift+E
+    bindings.push(KeyBinding {
+        modifiers: modkey,
+        keysym: key('e'),
+        action: Action::EmptyWorkspace,
+    });
+    bindings.push(KeyBinding {
+        modifiers: modkey | ModMask::SHIFT,
+        keysym: key('e'),
+        action: Action::MoveToEmptyWorkspace(false),
+    });
+    bindings.push(KeyBinding {
+        modifiers: modkey | ModMask::SHIFT | ModMask::CONTROL,
+        keysym: key('e'),
+        action: Action::MoveToEmptyWorkspace(true),
+    });
+    // Mod4+, и Mod4+.
+    bindings.push(KeyBinding {
+        modifiers: modkey,
+        keysym: key(','),
+        action: Action::FocusMonitor(MonitorTarget::Prev),
+    });
+    bindings.push(KeyBinding {
+        modifiers: modkey,
+        keysym: key('.'),
+        action: Action::FocusMonitor(MonitorTarget::Next),
+    });
+    // Mod4+Shift+, и Mod4+Shift+.
+    bindings.push(KeyBinding {
+        modifiers: modkey | ModMask::SHIFT,
+        keysym: key(','),
+        action: Action::MoveToMonitor(MonitorTarget::Prev),
+    });
+    bindings.push(KeyBinding {
+        modifiers: modkey | ModMask::SHIFT,
+        keysym: key('.'),
+        action: Action::MoveToMonitor(MonitorTarget::Next),
+    });
+    // Mod4+Shift+H/J/K/L, Mod4+Alt+H/J/K/L
+    for (keysym, direction) in [
+        (key('h'), Direction::Left),
+        (key('j'), Direction::Down),
+        (key('k'), Direction::Up),
+        (key('l'), Direction::Right),
+    ] {
+        bindings.push(KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym,
+            action: Action::MoveFloating(direction),
+        });
+        bindings.push(KeyBinding {
+            modifiers: modkey | ModMask::M1,
+            keysym,
+            action: Action::ResizeFloating(direction),
+        });
+    }
+    // Mod4+0
+    bindings.push(KeyBinding {
+        modifiers: modkey,
+        keysym: key('0'),
+        action: Action::ViewAll,
+    });
+    // Mod4+1..9, Mod4+Shift+1..9, Mod4+Ctrl+Shift+1..9, Mod4+Ctrl+1..9,
+    // Mod4+Alt+1..9
+    for i in 0..9 {
+        let keysym = key('1') + i as Keysym;
+        bindings.push(KeyBinding {
+            modifiers: modkey,
+            keysym,
+            action: Action::ViewWorkspace(i),
+        });
+        bindings.push(KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym,
+            action: Action::MoveToWorkspace(i, false),
+        });
+        bindings.push(KeyBinding {
+            modifiers: modkey | ModMask::SHIFT | ModMask::CONTROL,
+            keysym,
+            action: Action::MoveToWorkspace(i, true),
+        });
+        bindings.push(KeyBinding {
+            modifiers: modkey | ModMask::CONTROL,
+            keysym,
+            action: Action::ToggleView(i),
+        });
+        bindings.push(KeyBinding {
+            modifiers: modkey | ModMask::M1,
+            keysym,
+            action: Action::ToggleTag(i),
+        });
+    }
+    // Mod4+Shift+Return
+    if let Some(scratchpad) = config.scratchpads.first() {
+        bindings.push(KeyBinding {
+            modifiers: modkey | ModMask::SHIFT,
+            keysym: XK_RETURN,
+            action: Action::ToggleScratchpad(scratchpad.name.clone()),
+        });
+    }
+    // Alt+Tab, Alt+Shift+Tab. При модификаторе Mod1 это те же сочетания,
+    // что у Mod+Tab, и переключатель заменяет листание без захвата.
+    for (modifiers, forward) in [(ModMask::M1, true), (ModMask::M1 | ModMask::SHIFT, false)] {
+        bindings.retain(|b| b.modifiers != modifiers || b.keysym != XK_TAB);
+        bindings.push(KeyBinding {
+            modifiers,
+            keysym: XK_TAB,
+            action: Action::AltTab(forward),
+        });
+    }
+    for (combo, command) in &config.bindings {
+        let (modifiers, keysym) = match keys::parse_combo(combo, modkey) {
+            Ok(key) => key,
+            Err(e) => {
+                println!("Привязка {}: {}", combo, e);
+                continue;
+            }
+        };
+        bindings.retain(|b| b.modifiers != modifiers || b.keysym != keysym);
+        if command.trim() == "none" {
+            continue;
+        }
+        if !ipc::is_command(command) {
+            println!("Привязка {}: неизвестная команда {}", combo, command);
+            continue;
+        }
+        bindings.push(KeyBinding {
+            modifiers,
+            keysym,
+            action: Action::Command(command.clone()),
+        });
+    }
+    bindings
+}
+
+/// Запускает команду оболочки, не дожидаясь её завершения.
+fn spawn(command: &str) -> std::io::Result<()> {
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// Левый и правый Alt.
+const ALT_KEYSYMS: [Keysym; 2] = [XK_ALT_L, XK_ALT_R];
+
+/// Сколько ждать тишины после событий RandR, прежде чем заново
+/// определять мониторы: подключение выхода порождает серию событий.
+const RANDR_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Новое начало и длина стороны окна, когда её край сдвинут на `delta`:
+/// ближний к началу оси (`leading`) или дальний. Длина не меньше 1.
+fn resize_edge(start: i32, length: u32, delta: i32, leading: bool) -> (i32, u32) {
+    let length = length as i32;
+    if leading {
+        let delta = delta.min(length - 1);
+        (start + delta, (length - delta) as u32)
+    } else {
+        (start, (length + delta).max(1) as u32)
+    }
+}
+
+/// Без RandR и Xinerama весь экран считается одним монитором.
+fn screen_output(screen: &Screen) -> randr::Output {
+    randr::Output {
+        name: "default".into(),
+        area: Rect {
+            x: 0,
+            y: 0,
+            width: screen.width_in_pixels.into(),
+            height: screen.height_in_pixels.into(),
+        },
+        primary: true,
+        dpi: (screen.width_in_millimeters > 0).then(|| {
+            f32::from(screen.width_in_pixels) * 25.4 / f32::from(screen.width_in_millimeters)
+        }),
+    }
+}
+
+/// Основной монитор; если RandR его не назначил — первый.
+fn primary_monitor(monitors: &[Monitor]) -> usize {
+    monitors.iter().position(|m| m.primary).unwrap_or(0)
+}
+
+/// Перетаскивание плавающего окна мышью: откуда начали и где было окно.
+struct Drag {
+    window: Window,
+    pointer: (i32, i32),
+    geometry: Rect,
+    /// При изменении размера — тянущийся угол: (левый ли, верхний ли).
+    corner: Option<(bool, bool)>,
+    /// Оси, по которым меняется размер; за край окна тянется одна.
+    axes: (bool, bool),
+    /// Края, к которым прилипает перемещаемое окно: по x и по y.
+    edges: (Vec<i32>, Vec<i32>),
 }
 
-struct WindowManager<C: Connection> {
-    conn: C,
-    screen_num: usize,
-    layout: LayoutMode,
-    windows: Vec<u32>,
-}
+struct WindowManager<C: Connection> {
+    conn: C,
+    config: Config,
+    screen_num: usize,
+    monitors: Vec<Monitor>,
+    /// Монитор с фокусом: на нём работают команды и появляются новые окна.
+    selected: usize,
+    clients: HashMap<Window, Client>,
+    key_bindings: Vec<KeyBinding>,
+    keymap: Keymap,
+    /// Файл настроек из `--config`; без него файл ищется заново при
+    /// каждом перечитывании.
+    config_path: Option<PathBuf>,
+    tab_bars: TabBars,
+    bars: Bars,
+    /// Куда встанет следующее окно: рядом с каким окном и с какой стороны.
+    presel: Option<(Window, Direction)>,
+    /// Подсветка области, которую займёт преселектированное окно.
+    presel_hint: Window,
+    atoms: Atoms,
+    /// Открытый обзор столов; пока он есть, клавиши управляют им.
+    overview: Option<Overview>,
+    /// Идущее листание истории фокуса: снимок истории и позиция в нём.
+    /// Пока оно идёт, история не переупорядочивается.
+    mru_cycle: Option<(Vec<Window>, usize)>,
+    /// Идущий Alt-Tab: выбранное окно. Клавиатура захвачена до отпускания
+    /// Alt, а выбор до тех пор отмечен только рамкой.
+    alt_tab: Option<Window>,
+    /// Идущее перетаскивание; указатель захвачен до отпускания кнопки.
+    drag: Option<Drag>,
+    /// Последняя плавающая геометрия приложений по WM_CLASS.
+    floating_memory: FloatingMemory,
+    /// Окна, просящие внимания, в порядке появления флага.
+    urgent: Vec<Window>,
+    /// Окна всех мониторов и столов в порядке последнего фокуса.
+    focus_history: Vec<Window>,
+    /// Управляемые окна в порядке появления.
+    client_list: Vec<Window>,
+    /// Панели (`_NET_WM_WINDOW_TYPE_DOCK`): отображаются как есть, в
+    /// тайлинг не попадают и держатся над окнами.
+    docks: Vec<Window>,
+    /// Окна рабочего стола (`_NET_WM_WINDOW_TYPE_DESKTOP`): лежат под всеми.
+    desktop_windows: Vec<Window>,
+    /// Управляемые окна снизу вверх в пределах своих слоёв стопки;
+    /// поднятое окно уходит в конец.
+    stack: Vec<Window>,
+    /// Окно, которому последним отдан ввод.
+    input_focus: Option<Window>,
+    /// Время последнего нажатия клавиши, кнопки или входа указателя;
+    /// им помечается WM_TAKE_FOCUS (ICCCM 4.1.7).
+    user_time: Timestamp,
+    /// Опубликованный в `_NET_CURRENT_DESKTOP` стол.
+    current_desktop: Option<usize>,
+    /// Когда обработать накопившиеся изменения мониторов.
+    monitors_changed: Option<Instant>,
+    running: bool,
+}
+
+impl<C: Connection> WindowManager<C> {
+    fn new(
+        conn: C,
+        screen_num: usize,
+        config: Config,
+        config_path: Option<PathBuf>,
+    ) -> Result<Self, ReplyOrIdError> {
+        let screen = &conn.setup().roots[screen_num];
+        let tab_bars = TabBars::new(&conn, screen, config.colors)?;
+        let bars = Bars::new(&conn, screen, config.colors)?;
+        let presel_hint = conn.generate_id()?;
+        conn.create_window(
+            screen.root_depth,
+            presel_hint,
+            screen.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new()
+                .override_redirect(1)
+                .background_pixel(config.colors.presel),
+        )?;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let mut monitors: Vec<Monitor> = randr::detect(&conn, screen.root)?
+            .into_iter()
+            .map(|output| Monitor::new(&output, &config))
+            .collect();
+        if monitors.is_empty() {
+            monitors.push(Monitor::new(&screen_output(screen), &config));
+        }
+        let selected = primary_monitor(&monitors);
+        let keymap = Keymap::load(&conn)?;
+        for monitor in &monitors {
+            println!("Монитор {}: {:?}", monitor.name, monitor.area);
+        }
+        Ok(WindowManager {
+            conn,
+            screen_num,
+            monitors,
+            selected,
+            clients: HashMap::new(),
+            key_bindings: key_bindings(&config),
+            keymap,
+            config_path,
+            tab_bars,
+            bars,
+            presel: None,
+            presel_hint,
+            atoms,
+            overview: None,
+            mru_cycle: None,
+            alt_tab: None,
+            drag: None,
+            floating_memory: FloatingMemory::load(),
+            urgent: Vec::new(),
+            focus_history: Vec::new(),
+            client_list: Vec::new(),
+            docks: Vec::new(),
+            desktop_windows: Vec::new(),
+            stack: Vec::new(),
+            input_focus: None,
+            user_time: x11rb::CURRENT_TIME,
+            current_desktop: None,
+            monitors_changed: None,
+            running: true,
+            config,
+        })
+    }
+
+    fn monitor(&self) -> &Monitor {
+        &self.monitors[self.selected]
+    }
+
+    fn monitor_mut(&mut self) -> &mut Monitor {
+        &mut self.monitors[self.selected]
+    }
+
+    fn workspace(&self) -> &Workspace {
+        self.monitor().workspace()
+    }
+
+    fn workspace_mut(&mut self) -> &mut Workspace {
+        self.monitor_mut().workspace_mut()
+    }
+
+    /// Видно ли окно с тегами `client` на его мониторе. Вызванный
+    /// блокнот виден при любом виде.
+    fn shows(&self, client: &Client) -> bool {
+        !client.hidden
+            && (client.scratchpad.is_some()
+                || client.sticky
+                || client.tags & self.monitors[client.monitor].view != 0)
+    }
+
+    fn is_visible(&self, window: Window) -> bool {
+        self.clients
+            .get(&window)
+            .is_some_and(|client| self.shows(client))
+    }
+
+    fn visible_windows(&self) -> Vec<Window> {
+        self.monitor().visible_windows()
+    }
+
+    /// Отображает окна видимых тегов и снимает с экрана остальные.
+    fn show_hide(&mut self) -> Result<(), ReplyOrIdError> {
+        let windows: Vec<(Window, bool)> = self
+            .clients
+            .iter()
+            .map(|(&window, client)| (window, self.shows(client)))
+            .collect();
+        for (window, shown) in windows {
+            if shown {
+                self.map(window)?;
+            } else {
+                self.unmap(window)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn map(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        if let Some(client) = self.clients.get_mut(&window)
+            && !client.mapped
+        {
+            client.mapped = true;
+            self.conn.map_window(window)?;
+            icccm::set_wm_state(&self.conn, window, &self.atoms, WmState::Normal)?;
+        }
+        Ok(())
+    }
+
+    /// Снимает окно с экрана, запоминая, что его UnmapNotify — наш.
+    /// Спрятанное менеджером окно считается свёрнутым.
+    fn unmap(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        if let Some(client) = self.clients.get_mut(&window)
+            && client.mapped
+        {
+            client.mapped = false;
+            client.pending_unmaps += 1;
+            self.conn.unmap_window(window)?;
+            icccm::set_wm_state(&self.conn, window, &self.atoms, WmState::Iconic)?;
+        }
+        Ok(())
+    }
+
+    /// UnmapNotify: окно, снявшее себя с экрана само, больше не управляется.
+    /// Его состояния снимаются, чтобы при новом отображении оно считалось
+    /// новым.
+    fn handle_unmap_notify(&mut self, event: &UnmapNotifyEvent) -> Result<(), ReplyOrIdError> {
+        self.desktop_windows.retain(|&w| w != event.window);
+        self.forget_dock(event.window)?;
+        let Some(client) = self.clients.get_mut(&event.window) else {
+            return Ok(());
+        };
+        if client.pending_unmaps > 0 {
+            client.pending_unmaps -= 1;
+            return Ok(());
+        }
+        println!("Окно {} убрало себя с экрана", event.window);
+        icccm::set_wm_state(&self.conn, event.window, &self.atoms, WmState::Withdrawn)?;
+        for property in [self.atoms._NET_WM_STATE, self.atoms._NET_WM_DESKTOP] {
+            self.conn.delete_property(event.window, property)?;
+        }
+        self.unmanage(event.window)
+    }
+
+    /// Убирает исчезнувшую панель и возвращает окнам её место.
+    fn forget_dock(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        if !self.docks.contains(&window) {
+            return Ok(());
+        }
+        self.docks.retain(|&w| w != window);
+        self.update_struts()?;
+        self.arrange_windows()
+    }
+
+    /// Пересчитывает занятые панелями края мониторов и публикует рабочие
+    /// области столов.
+    fn update_struts(&mut self) -> Result<(), ReplyOrIdError> {
+        let screen = &self.conn.setup().roots[self.screen_num];
+        let size = (
+            u32::from(screen.width_in_pixels),
+            u32::from(screen.height_in_pixels),
+        );
+        let struts: Vec<[u32; 12]> = self
+            .docks
+            .iter()
+            .filter_map(|&dock| ewmh::strut(&self.conn, dock, &self.atoms))
+            .collect();
+        for monitor in &mut self.monitors {
+            monitor.struts = Struts::default();
+            for strut in &struts {
+                monitor.reserve(strut, size);
+            }
+        }
+        self.update_desktops()
+    }
+
+    /// Расставляет видимые окна всех мониторов.
+    fn arrange_windows(&mut self) -> Result<(), ReplyOrIdError> {
+        let mut bars = Vec::new();
+        for index in 0..self.monitors.len() {
+            bars.extend(self.arrange_monitor(index)?);
+        }
+        self.update_frame_extents()?;
+
+        let bars = bars
+            .into_iter()
+            .map(|bar| {
+                let titles = bar.tabs.iter().map(|&w| self.window_title(w)).collect();
+                (bar, titles)
+            })
+            .collect();
+        let focused = self.workspace().focused;
+        let screen = &self.conn.setup().roots[self.screen_num];
+        self.tab_bars.update(&self.conn, screen, bars, focused)?;
+        self.update_bars()
+    }
+
+    /// Перерисовывает панели мониторов по текущему состоянию столов.
+    /// Внешним пейджерам заодно сообщаются текущий стол и столы окон.
+    fn update_bars(&mut self) -> Result<(), ReplyOrIdError> {
+        self.update_current_desktop()?;
+        self.update_window_desktops()?;
+        let contents = self
+            .monitors
+            .iter()
+            .enumerate()
+            .filter(|(_, monitor)| monitor.bar_height > 0)
+            .map(|(index, monitor)| BarContent {
+                rect: monitor.bar_rect(),
+                selected: index == self.selected,
+                workspaces: monitor
+                    .workspaces
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ws)| BarWorkspace {
+                        label: ws.label(i),
+                        current: i == monitor.current,
+                        visible: monitor.view & (1 << i) != 0,
+                        occupied: !ws.tree.windows().is_empty(),
+                        urgent: ws
+                            .tree
+                            .windows()
+                            .iter()
+                            .any(|w| self.clients.get(w).is_some_and(|c| c.urgent)),
+                    })
+                    .collect(),
+                title: monitor
+                    .workspace()
+                    .focused
+                    .map(|w| self.window_title(w))
+                    .unwrap_or_default(),
+            })
+            .collect();
+        let screen = &self.conn.setup().roots[self.screen_num];
+        self.bars.update(&self.conn, screen, contents)
+    }
+
+    /// Публикует `_NET_FRAME_EXTENTS` окон, чья рамка сменилась: у окон на
+    /// весь экран её нет, у остальных она зависит от правила окна и
+    /// масштаба монитора.
+    fn update_frame_extents(&mut self) -> Result<(), ReplyOrIdError> {
+        for (&window, client) in &mut self.clients {
+            let frame = if client.fullscreen {
+                0
+            } else {
+                let border = client.border.unwrap_or(self.config.border_width);
+                self.monitors[client.monitor].scaled(border)
+            };
+            if client.frame != Some(frame) {
+                client.frame = Some(frame);
+                ewmh::set_frame_extents(&self.conn, window, &self.atoms, frame)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Расставляет видимые окна монитора по раскладке его основного стола
+    /// и возвращает нужные ему полосы вкладок.
+    fn arrange_monitor(&self, index: usize) -> Result<Vec<TabBar>, ReplyOrIdError> {
+        let monitor = &self.monitors[index];
+        // Половина зазора отступает от краёв области, другая — от краёв
+        // каждой ячейки: между соседями и у краёв зазоры равны.
+        let gap = monitor.scaled(self.config.gap);
+        let area = monitor.work_area().shrink(gap - gap / 2);
+        let windows = monitor.visible_windows();
+        let workspace = monitor.workspace();
+
+        let mut arrangement = match (workspace.layout, workspace.layout.layout()) {
+            (_, Some(layout)) => {
+                let windows: Vec<Window> = windows
+                    .iter()
+                    .copied()
+                    .filter(|w| self.clients.get(w).is_some_and(Client::tiled))
+                    .collect();
+                let clients: Vec<&Client> = windows.iter().map(|w| &self.clients[w]).collect();
+                let source = workspace.transform.source_area(area);
+                Arrangement {
+                    windows: windows
+                        .iter()
+                        .copied()
+                        .zip(layout.arrange(source, &clients, &workspace.params))
+                        .filter_map(|(w, rect)| Some((w, workspace.transform.apply(area, rect?))))
+                        .collect(),
+                    tab_bars: Vec::new(),
+                }
+            }
+            (LayoutMode::Floating, None) => Arrangement {
+                windows: windows
+                    .iter()
+                    .filter_map(|&w| Some((w, self.clients.get(&w)?.float_geometry)))
+                    .collect(),
+                tab_bars: Vec::new(),
+            },
+            // Окна других видимых столов дописываются в конец копии дерева,
+            // плавающие из неё убираются.
+            (_, None) => {
+                let mut combined = workspace.tree.clone();
+                for &window in &windows {
+                    if !workspace.tree.contains(window) {
+                        combined.push(window);
+                    }
+                }
+                for &window in &windows {
+                    if self.clients.get(&window).is_some_and(|c| !c.tiled()) {
+                        combined.remove(window);
+                    }
+                }
+                combined.arrange(area, monitor.scaled(TAB_BAR_HEIGHT))
+            }
+        };
+        if !matches!(workspace.layout, LayoutMode::Floating) {
+            arrangement.windows.extend(windows.iter().filter_map(|w| {
+                let client = self.clients.get(w).filter(|c| !c.tiled())?;
+                Some((*w, client.float_geometry))
+            }));
+        }
+
+        // Окна неактивных вкладок и скрытые карты колоды уводятся за пределы экрана.
+        let screen = &self.conn.setup().roots[self.screen_num];
+        for &window in &windows {
+            if !arrangement.windows.iter().any(|(w, _)| *w == window) {
+                self.conn.configure_window(
+                    window,
+                    &ConfigureWindowAux::new().x(-2 * i32::from(screen.width_in_pixels)),
+                )?;
+            }
+        }
+
+        // Ячейка тайлинга включает рамку; плавающее окно сохраняет свой
+        // размер, и рамка рисуется вокруг него.
+        let layout = workspace.layout;
+        for &(window, slot) in &arrangement.windows {
+            let client = self.clients.get(&window);
+            let border = client.map_or(self.border_width(index), |c| self.client_border(c));
+            if client.is_some_and(|c| c.fullscreen) {
+                let area = monitor.area;
+                self.conn.configure_window(
+                    window,
+                    &ConfigureWindowAux::new()
+                        .x(area.x)
+                        .y(area.y)
+                        .width(area.width)
+                        .height(area.height)
+                        .border_width(0),
+                )?;
+                continue;
+            }
+            let floating =
+                matches!(layout, LayoutMode::Floating) || client.is_some_and(|c| c.floating);
+            let mut rect = if floating {
+                slot
+            } else {
+                let slot = slot.shrink(gap / 2);
+                let inner = Rect {
+                    width: slot.width.saturating_sub(2 * border).max(1),
+                    height: slot.height.saturating_sub(2 * border).max(1),
+                    ..slot
+                };
+                client.map_or(inner, |client| client.fit(inner))
+            };
+            if let Some(client) = client {
+                (rect.width, rect.height) = client.constrain(rect.width, rect.height);
+                if !floating && self.config.size_increments {
+                    let (width, height) = client.apply_increments(rect.width, rect.height);
+                    if self.config.center_increment_slack {
+                        rect.x += ((rect.width - width) / 2) as i32;
+                        rect.y += ((rect.height - height) / 2) as i32;
+                    }
+                    (rect.width, rect.height) = (width, height);
+                }
+            }
+            self.conn.configure_window(
+                window,
+                &ConfigureWindowAux::new()
+                    .x(rect.x)
+                    .y(rect.y)
+                    .width(rect.width)
+                    .height(rect.height)
+                    .border_width(border),
+            )?;
+        }
+
+        self.restack(index)?;
+        Ok(arrangement.tab_bars)
+    }
+
+    fn window_geometry(&self, window: Window) -> Option<Rect> {
+        let geometry = self.conn.get_geometry(window).ok()?.reply().ok()?;
+        Some(Rect {
+            x: geometry.x.into(),
+            y: geometry.y.into(),
+            width: geometry.width.into(),
+            height: geometry.height.into(),
+        })
+    }
+
+    /// Для плавающих и неуправляемых окон запрос выполняется
+    /// как есть. Тайловым окнам отвечаем синтетическим ConfigureNotify
+    /// с их настоящей геометрией, как требует ICCCM.
+    fn handle_configure_request(
+        &mut self,
+        event: &ConfigureRequestEvent,
+    ) -> Result<(), ReplyOrIdError> {
+        let managed = self.clients.get(&event.window).map(|c| c.monitor);
+        if let Some(geometry) = self
+            .clients
+            .get_mut(&event.window)
+            .map(|c| &mut c.float_geometry)
+        {
+            let mask = event.value_mask;
+            if mask.contains(ConfigWindow::X) {
+                geometry.x = event.x.into();
+            }
+            if mask.contains(ConfigWindow::Y) {
+                geometry.y = event.y.into();
+            }
+            if mask.contains(ConfigWindow::WIDTH) {
+                geometry.width = event.width.into();
+            }
+            if mask.contains(ConfigWindow::HEIGHT) {
+                geometry.height = event.height.into();
+            }
+        }
+
+        // Окно на весь экран своё положение не выбирает.
+        let floating = self.floats(event.window)
+            && !self
+                .clients
+                .get(&event.window)
+                .is_some_and(|c| c.fullscreen);
+        // Порядок в стопке управляемых окон выбирает менеджер.
+        let stacking = event.value_mask.contains(ConfigWindow::STACK_MODE);
+        if managed.is_none() || floating {
+            let mut aux = ConfigureWindowAux::from_configure_request(event);
+            if managed.is_some() {
+                aux = aux.sibling(None).stack_mode(None);
+            }
+            self.conn.configure_window(event.window, &aux)?;
+            if floating {
+                self.follow_floating_monitor(event.window)?;
+            }
+            if managed.is_some() && stacking {
+                self.handle_stack_request(event.window, event.stack_mode)?;
+            }
+            return Ok(());
+        }
+        if stacking {
+            self.handle_stack_request(event.window, event.stack_mode)?;
+        }
+
+        let Some(rect) = self.window_geometry(event.window) else {
+            return Ok(());
+        };
+        let notify = ConfigureNotifyEvent {
+            response_type: CONFIGURE_NOTIFY_EVENT,
+            sequence: 0,
+            event: event.window,
+            window: event.window,
+            above_sibling: x11rb::NONE,
+            x: rect.x as i16,
+            y: rect.y as i16,
+            width: rect.width as u16,
+            height: rect.height as u16,
+            border_width: self.client_border(&self.clients[&event.window]) as u16,
+            override_redirect: false,
+        };
+        self.conn
+            .send_event(false, event.window, EventMask::STRUCTURE_NOTIFY, notify)?;
+        Ok(())
+    }
+
+    /// Заголовок окна из `_NET_WM_NAME` или WM_NAME; для исчезнувших окон —
+    /// пустая строка.
+    fn window_title(&self, window: Window) -> String {
+        ewmh::window_name(&self.conn, window, &self.atoms)
+    }
+
+    /// Берёт окно под управление и добавляет его в дерево: в BSP-режиме
+    /// оно делит сфокусированное, в остальных раскладках встаёт в конец
+    /// списка. Подходящие правила выбирают окну стол, монитор, плавание,
+    /// полноэкранность, рамку и фокус.
+    /// Исчезнувшие к этому моменту окна пропускаются.
+    fn manage(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        // Панели и уведомления расставляют себя сами.
+        let types = ewmh::window_types(&self.conn, window, &self.atoms);
+        if types.contains(&self.atoms._NET_WM_WINDOW_TYPE_DOCK) {
+            println!("Окно {} — панель", window);
+            // Страт панели может меняться, пока она работает.
+            self.conn.change_window_attributes(
+                window,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )?;
+            self.docks.push(window);
+            return self.update_struts();
+        }
+        if types.contains(&self.atoms._NET_WM_WINDOW_TYPE_DESKTOP) {
+            self.desktop_windows.push(window);
+            return Ok(());
+        }
+        if types.contains(&self.atoms._NET_WM_WINDOW_TYPE_NOTIFICATION) {
+            return Ok(());
+        }
+        let Some(geometry) = self.window_geometry(window) else {
+            return Ok(());
+        };
+        let size_hints = WmSizeHints::get_normal_hints(&self.conn, window)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .flatten();
+        // Смена заголовка должна доходить до панели, вход указателя —
+        // до фокуса, смена фокуса — до цвета рамки.
+        self.conn.change_window_attributes(
+            window,
+            &ChangeWindowAttributesAux::new()
+                .event_mask(
+                    EventMask::PROPERTY_CHANGE | EventMask::ENTER_WINDOW | EventMask::FOCUS_CHANGE,
+                )
+                .border_pixel(self.config.border_unfocused),
+        )?;
+        self.grab_buttons(window, false)?;
+        // Окно на скрытом столе так и останется свёрнутым.
+        icccm::set_wm_state(&self.conn, window, &self.atoms, WmState::Iconic)?;
+        let mut client = Client::new(geometry, size_hints);
+        // Окна фиксированного размера, диалоги, палитры и заставки в
+        // тайлинг не встают.
+        client.floating = client.fixed_size()
+            || [
+                self.atoms._NET_WM_WINDOW_TYPE_DIALOG,
+                self.atoms._NET_WM_WINDOW_TYPE_UTILITY,
+                self.atoms._NET_WM_WINDOW_TYPE_SPLASH,
+            ]
+            .iter()
+            .any(|t| types.contains(t));
+        let states = ewmh::wm_state(&self.conn, window, &self.atoms);
+        client.above = states.contains(&self.atoms._NET_WM_STATE_ABOVE);
+        client.fullscreen = states.contains(&self.atoms._NET_WM_STATE_FULLSCREEN);
+        if let Some(class) = WmClass::get(&self.conn, window)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .flatten()
+        {
+            client.instance = String::from_utf8_lossy(class.instance()).into_owned();
+            client.class = String::from_utf8_lossy(class.class()).into_owned();
+        }
+        client.pid = ewmh::pid(&self.conn, window, &self.atoms);
+        client.monitor = self.selected;
+        let window_type = [
+            (self.atoms._NET_WM_WINDOW_TYPE_DIALOG, WindowType::Dialog),
+            (self.atoms._NET_WM_WINDOW_TYPE_UTILITY, WindowType::Utility),
+            (self.atoms._NET_WM_WINDOW_TYPE_SPLASH, WindowType::Splash),
+        ]
+        .into_iter()
+        .find(|(atom, _)| types.contains(atom))
+        .map_or(WindowType::Normal, |(_, t)| t);
+        let actions = rules::evaluate(
+            &self.config.rules,
+            &Properties {
+                class: &client.class,
+                instance: &client.instance,
+                title: &ewmh::window_name(&self.conn, window, &self.atoms),
+                role: &icccm::window_role(&self.conn, window, &self.atoms),
+                window_type,
+            },
+        );
+        client.floating = actions.floating.unwrap_or(client.floating);
+        client.fullscreen = actions.fullscreen.unwrap_or(client.fullscreen);
+        client.border = actions.border;
+        client.no_focus = actions.no_focus;
+        (client.input, client.take_focus) = self.input_model(window);
+        client.urgent = self.wants_attention(window)
+            || states.contains(&self.atoms._NET_WM_STATE_DEMANDS_ATTENTION);
+        if client.urgent {
+            self.urgent.push(window);
+            self.conn.change_window_attributes(
+                window,
+                &ChangeWindowAttributesAux::new().border_pixel(self.config.border_urgent),
+            )?;
+        }
+
+        // Первое окно с классом блокнота становится им и в столы не попадает.
+        let scratchpad = self.config.scratchpads.iter().find(|sp| {
+            (sp.class == client.class || sp.class == client.instance)
+                && !self
+                    .clients
+                    .values()
+                    .any(|c| c.scratchpad.as_deref() == Some(sp.name.as_str()))
+        });
+        if let Some(scratchpad) = scratchpad {
+            client.scratchpad = Some(scratchpad.name.clone());
+            self.clients.insert(window, client);
+            return self.place_scratchpad(window);
+        }
+
+        // Диалоги плавают и открываются на столах родителя, а без
+        // известного родителя — на основном мониторе.
+        if let Some(parent) = self.transient_for(window) {
+            client.floating = actions.floating.unwrap_or(true);
+            client.transient_for = Some(parent);
+            let (monitor, tags) = match self.clients.get(&parent) {
+                Some(parent) if parent.scratchpad.is_none() => (parent.monitor, parent.tags),
+                _ => {
+                    let primary = primary_monitor(&self.monitors);
+                    (primary, 1 << self.monitors[primary].current)
+                }
+            };
+            client.monitor = monitor;
+            self.clients.insert(window, client);
+            self.set_tags(window, tags);
+            return Ok(());
+        }
+
+        // Монитор из правила ищется по имени выхода, стол — на нём.
+        let target_monitor = actions
+            .monitor
+            .as_deref()
+            .and_then(|name| self.monitors.iter().position(|m| m.name == name))
+            .unwrap_or(self.selected);
+        client.monitor = target_monitor;
+        let (config, monitor) = (&self.config, &mut self.monitors[target_monitor]);
+        let count = monitor.workspaces.len();
+        let target = actions.workspace.as_ref().and_then(|(name, switch)| {
+            let index = monitor.resolve_workspace(name, config)?;
+            Some((index, *switch))
+        });
+        if monitor.workspaces.len() != count {
+            self.update_desktops()?;
+        }
+
+        let current = self.monitors[target_monitor].current;
+        let (index, switch) = target.unwrap_or((current, false));
+        client.tags = 1 << index;
+        self.clients.insert(window, client);
+        if index != current || target_monitor != self.selected {
+            println!(
+                "Окно {} по правилу отправлено на стол {} монитора {}",
+                window,
+                index + 1,
+                self.monitors[target_monitor].name
+            );
+            let monitor = &mut self.monitors[target_monitor];
+            let area = monitor.work_area();
+            let workspace = &mut monitor.workspaces[index];
+            workspace.insert(window, area);
+            workspace.focused = Some(window);
+            if switch {
+                self.select_monitor(target_monitor)?;
+                if index != self.monitor().current {
+                    return self.view_workspace(index);
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some((target, direction)) = self.presel.take() {
+            self.conn.unmap_window(self.presel_hint)?;
+            self.workspace_mut()
+                .tree
+                .insert_beside(target, window, direction);
+            return Ok(());
+        }
+        let area = self.monitor().work_area();
+        self.workspace_mut().insert(window, area);
+        Ok(())
+    }
+
+    /// Стоит ли у окна флаг urgency в WM_HINTS. Окну с фокусом внимание
+    /// уже уделено, его флаг не считается.
+    fn wants_attention(&self, window: Window) -> bool {
+        WmHints::get(&self.conn, window)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .flatten()
+            .is_some_and(|hints| hints.urgent)
+            && self.input_focus != Some(window)
+    }
+
+    /// Модель ввода окна по ICCCM: принимает ли оно фокус от менеджера
+    /// (без WM_HINTS — да) и просит ли WM_TAKE_FOCUS.
+    fn input_model(&self, window: Window) -> (bool, bool) {
+        let input = WmHints::get(&self.conn, window)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .flatten()
+            .and_then(|hints| hints.input)
+            .unwrap_or(true);
+        let take_focus = icccm::supports(&self.conn, window, &self.atoms, self.atoms.WM_TAKE_FOCUS);
+        (input, take_focus)
+    }
+
+    /// Перечитывает флаг urgency после изменения WM_HINTS.
+    fn update_urgency(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        let urgent = self.wants_attention(window);
+        if urgent {
+            println!("Окно {} просит внимания", window);
+        }
+        self.set_urgent(window, urgent)
+    }
+
+    /// Ставит окну флаг внимания или снимает его: окно попадает в список
+    /// просящих внимания или уходит из него, рамка перекрашивается, флаг
+    /// публикуется в `_NET_WM_STATE_DEMANDS_ATTENTION` и на панели.
+    fn set_urgent(&mut self, window: Window, urgent: bool) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        if client.urgent == urgent {
+            return Ok(());
+        }
+        client.urgent = urgent;
+        self.urgent.retain(|&w| w != window);
+        if urgent {
+            self.urgent.push(window);
+        }
+        self.update_net_wm_state(window)?;
+        self.update_border(window, self.input_focus == Some(window))?;
+        self.update_bars()
+    }
+
+    /// Перекрашивает рамку окна: с фокусом, просящего внимания или обычного.
+    fn update_border(&self, window: Window, focused: bool) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+        let color = if focused {
+            self.config.border_focused
+        } else if client.urgent {
+            self.config.border_urgent
+        } else {
+            self.config.border_unfocused
+        };
+        self.conn.change_window_attributes(
+            window,
+            &ChangeWindowAttributesAux::new().border_pixel(color),
+        )?;
+        Ok(())
+    }
+
+    /// FocusIn и FocusOut окна. Переходы фокуса из-за захвата клавиатуры
+    /// (Alt-Tab, обзор) и внутри окна рамку не трогают.
+    fn handle_focus_change(
+        &self,
+        event: &FocusInEvent,
+        focused: bool,
+    ) -> Result<(), ReplyOrIdError> {
+        if matches!(event.mode, NotifyMode::GRAB | NotifyMode::UNGRAB)
+            || matches!(event.detail, NotifyDetail::POINTER | NotifyDetail::INFERIOR)
+        {
+            return Ok(());
+        }
+        self.update_border(event.event, focused)
+    }
+
+    /// Толщина рамки окон монитора с учётом его масштаба.
+    fn border_width(&self, monitor: usize) -> u32 {
+        self.monitors[monitor].scaled(self.config.border_width)
+    }
+
+    /// Толщина рамки окна: из его правила или общая, в масштабе монитора.
+    fn client_border(&self, client: &Client) -> u32 {
+        let border = client.border.unwrap_or(self.config.border_width);
+        self.monitors[client.monitor].scaled(border)
+    }
+
+    /// Переходит к окну, последним попросившему внимания.
+    fn focus_urgent(&mut self) -> Result<(), ReplyOrIdError> {
+        match self.urgent.last() {
+            Some(&window) => self.activate(window),
+            None => Ok(()),
+        }
+    }
+
+    /// Фокусирует окно, где бы оно ни было: если окно не видно, выбирает
+    /// его монитор и первый из его столов.
+    fn activate(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+        if let Some(name) = client.scratchpad.clone() {
+            if client.hidden || client.monitor != self.selected {
+                return self.toggle_scratchpad(&name);
+            }
+            return self.focus(Some(window));
+        }
+        let (monitor, index) = (client.monitor, client.tags.trailing_zeros() as usize);
+        if !self.is_visible(window) {
+            self.selected = monitor;
+            self.view_workspace(index)?;
+        }
+        self.focus(Some(window))
+    }
+
+    /// Отнимет ли фокус окно, чьё действие пользователя было в момент
+    /// `time`: пользователь с тех пор работал в окне с фокусом. Нулевое
+    /// время по EWMH значит «не фокусировать вовсе».
+    fn steals_focus(&self, time: Option<u32>) -> bool {
+        if !self.config.focus_stealing_prevention {
+            return false;
+        }
+        let Some(time) = time else {
+            return false;
+        };
+        if time == 0 {
+            return true;
+        }
+        let focused = self
+            .input_focus
+            .and_then(|w| ewmh::user_time(&self.conn, w, &self.atoms));
+        // Время X переполняется, сравнивать нужно по модулю.
+        focused.is_some_and(|focused| (focused.wrapping_sub(time) as i32) > 0)
+    }
+
+    /// Фокус только что взятому под управление окну по `new_window_focus`.
+    /// Вызванный блокнот фокусируется всегда, окно с `no_focus` из правила — никогда.
+    fn focus_new_window(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        let scratchpad = self
+            .clients
+            .get(&window)
+            .is_some_and(|client| client.scratchpad.is_some());
+        let visible = self.is_visible(window);
+        if scratchpad {
+            return if visible {
+                self.focus(Some(window))
+            } else {
+                Ok(())
+            };
+        }
+        if self
+            .clients
+            .get(&window)
+            .is_some_and(|client| client.no_focus)
+        {
+            return Ok(());
+        }
+        match self.config.new_window_focus {
+            NewWindowFocus::Never => return self.deny_focus(window),
+            NewWindowFocus::CurrentWorkspace if !visible => return Ok(()),
+            NewWindowFocus::Always | NewWindowFocus::CurrentWorkspace => {}
+        }
+        let time = ewmh::user_time(&self.conn, window, &self.atoms)
+            .or_else(|| ewmh::startup_time(&self.conn, window, &self.atoms));
+        if self.steals_focus(time) {
+            return self.deny_focus(window);
+        }
+        self.activate(window)
+    }
+
+    /// Помечает окно urgent вместо того, чтобы отдать ему фокус.
+    fn deny_focus(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        println!("Окно {} не получило фокус и помечено urgent", window);
+        self.set_urgent(window, true)
+    }
+
+    /// Запросы EWMH от пейджеров и приложений: смена стола, закрытие,
+    /// перемещение окна, его состояния и стол. `_NET_ACTIVE_WINDOW`:
+    /// пейджерам фокус отдаётся всегда, приложениям — если запрос не
+    /// запоздал относительно действий пользователя.
+    fn handle_client_message(&mut self, event: &ClientMessageEvent) -> Result<(), ReplyOrIdError> {
+        // Окно ещё не отображено и спрашивает, какой будет его рамка.
+        if event.type_ == self.atoms._NET_REQUEST_FRAME_EXTENTS {
+            let border = self.border_width(self.selected);
+            return ewmh::set_frame_extents(&self.conn, event.window, &self.atoms, border);
+        }
+        if event.type_ == self.atoms._NET_CURRENT_DESKTOP {
+            let [desktop, ..] = event.data.as_data32();
+            return self.view_desktop(desktop as usize);
+        }
+        if !self.clients.contains_key(&event.window) {
+            return Ok(());
+        }
+        if event.type_ == self.atoms._NET_WM_STATE {
+            return self.handle_wm_state_message(event);
+        }
+        if event.type_ == self.atoms._NET_CLOSE_WINDOW {
+            return self.close_window(event.window);
+        }
+        if event.type_ == self.atoms._NET_MOVERESIZE_WINDOW {
+            return self.handle_moveresize_message(event);
+        }
+        if event.type_ == self.atoms._NET_RESTACK_WINDOW {
+            let [_, _, detail, ..] = event.data.as_data32();
+            return self.handle_stack_request(event.window, StackMode::from(detail as u8));
+        }
+        if event.type_ == self.atoms._NET_WM_MOVERESIZE {
+            return self.handle_wm_moveresize_message(event);
+        }
+        if event.type_ == self.atoms._NET_WM_DESKTOP {
+            let [desktop, ..] = event.data.as_data32();
+            return self.move_to_desktop(event.window, desktop);
+        }
+        if event.type_ != self.atoms._NET_ACTIVE_WINDOW {
+            return Ok(());
+        }
+        let [source, time, ..] = event.data.as_data32();
+        if source != 2 && self.steals_focus(Some(time).filter(|&t| t != 0)) {
+            return self.deny_focus(event.window);
+        }
+        self.activate(event.window)
+    }
+
+    /// `_NET_MOVERESIZE_WINDOW`: внешняя программа двигает окно или меняет
+    /// его размер. Биты 8–11 первого слова говорят, какие из x, y, ширины
+    /// и высоты заданы. Тайловые окна и окна на весь экран стоят, где
+    /// их поставила раскладка.
+    fn handle_moveresize_message(
+        &mut self,
+        event: &ClientMessageEvent,
+    ) -> Result<(), ReplyOrIdError> {
+        let [flags, x, y, width, height] = event.data.as_data32();
+        if !self.floats(event.window) {
+            return Ok(());
+        }
+        let Some(client) = self.clients.get_mut(&event.window) else {
+            return Ok(());
+        };
+        if client.fullscreen {
+            return Ok(());
+        }
+        let given = |bit: u32| flags & (1 << bit) != 0;
+        let rect = &mut client.float_geometry;
+        if given(8) {
+            rect.x = x as i32;
+        }
+        if given(9) {
+            rect.y = y as i32;
+        }
+        if given(10) {
+            rect.width = width.max(1);
+        }
+        if given(11) {
+            rect.height = height.max(1);
+        }
+        let rect = *rect;
+        self.conn.configure_window(
+            event.window,
+            &ConfigureWindowAux::new()
+                .x(rect.x)
+                .y(rect.y)
+                .width(rect.width)
+                .height(rect.height),
+        )?;
+        self.follow_floating_monitor(event.window)
+    }
+
+    /// `_NET_WM_MOVERESIZE`: приложение с собственным заголовком просит
+    /// начать перетаскивание окна или его края мышью. Направления 0–7 —
+    /// ручки по часовой стрелке от левого верхнего угла, 8 — перемещение,
+    /// 11 — отмена; перетаскивание с клавиатуры не поддерживается.
+    fn handle_wm_moveresize_message(
+        &mut self,
+        event: &ClientMessageEvent,
+    ) -> Result<(), ReplyOrIdError> {
+        let [x, y, direction, ..] = event.data.as_data32();
+        let (corner, axes) = match direction {
+            0 => (Some((true, true)), (true, true)),
+            1 => (Some((false, true)), (false, true)),
+            2 => (Some((false, true)), (true, true)),
+            3 => (Some((false, false)), (true, false)),
+            4 => (Some((false, false)), (true, true)),
+            5 => (Some((false, false)), (false, true)),
+            6 => (Some((true, false)), (true, true)),
+            7 => (Some((true, false)), (true, false)),
+            8 => (None, (true, true)),
+            11 => return self.finish_drag(),
+            _ => return Ok(()),
+        };
+        // Координаты в CARD32, но левее и выше начала корневого окна они
+        // отрицательны.
+        self.begin_drag(event.window, (x as i32, y as i32), corner, axes)
+    }
+
+    /// Запрос смены `_NET_WM_STATE`: действие (0 — снять, 1 — включить,
+    /// 2 — переключить) и до двух состояний.
+    fn handle_wm_state_message(
+        &mut self,
+        event: &ClientMessageEvent,
+    ) -> Result<(), ReplyOrIdError> {
+        let [action, first, second, ..] = event.data.as_data32();
+        let Some(client) = self.clients.get(&event.window) else {
+            return Ok(());
+        };
+        let change = |current: bool| match action {
+            0 => false,
+            1 => true,
+            2 => !current,
+            _ => current,
+        };
+        let states = [first, second];
+        let mut above = client.above;
+        let mut fullscreen = client.fullscreen;
+        let mut attention = client.urgent;
+        let (mut horizontal, mut vertical) = client.maximized;
+        for state in states {
+            if state == self.atoms._NET_WM_STATE_ABOVE {
+                above = change(above);
+            } else if state == self.atoms._NET_WM_STATE_FULLSCREEN {
+                fullscreen = change(fullscreen);
+            } else if state == self.atoms._NET_WM_STATE_DEMANDS_ATTENTION {
+                attention = change(attention);
+            } else if state == self.atoms._NET_WM_STATE_MAXIMIZED_HORZ {
+                horizontal = change(horizontal);
+            } else if state == self.atoms._NET_WM_STATE_MAXIMIZED_VERT {
+                vertical = change(vertical);
+            }
+        }
+        if states.contains(&self.atoms._NET_WM_STATE_ABOVE) {
+            self.set_above(event.window, above)?;
+        }
+        if states.contains(&self.atoms._NET_WM_STATE_FULLSCREEN) {
+            self.set_fullscreen(event.window, fullscreen)?;
+        }
+        // Окну с фокусом внимание уже уделено.
+        if states.contains(&self.atoms._NET_WM_STATE_DEMANDS_ATTENTION) {
+            let attention = attention && self.input_focus != Some(event.window);
+            self.set_urgent(event.window, attention)?;
+        }
+        self.set_maximized(event.window, (horizontal, vertical))
+    }
+
+    /// Родитель диалога из WM_TRANSIENT_FOR.
+    fn transient_for(&self, window: Window) -> Option<Window> {
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                window,
+                AtomEnum::WM_TRANSIENT_FOR,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )
+            .ok()?
+            .reply()
+            .ok()?;
+        reply.value32()?.next().filter(|&w| w != x11rb::NONE)
+    }
+
+    /// Забывает уничтоженное окно, на каком бы столе оно ни было.
+    /// Фокус стола переходит к предыдущему по истории фокуса окну, а без
+    /// истории — к соседу по списку.
+    fn unmanage(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        self.desktop_windows.retain(|&w| w != window);
+        self.forget_dock(window)?;
+        self.remember_floating(window);
+        let visible = self.is_visible(window);
+        let Some(client) = self.clients.remove(&window) else {
+            return Ok(());
+        };
+        self.urgent.retain(|&w| w != window);
+        self.focus_history.retain(|&w| w != window);
+        self.client_list.retain(|&w| w != window);
+        self.stack.retain(|&w| w != window);
+        self.update_client_list()?;
+        if self.presel.is_some_and(|(w, _)| w == window) {
+            self.cancel_preselect()?;
+        }
+
+        let was_focused = client.scratchpad.is_some()
+            || client.monitor == self.selected && self.workspace().focused == Some(window);
+        for workspace in &mut self.monitors[client.monitor].workspaces {
+            workspace.remove(window);
+        }
+        if visible {
+            if was_focused {
+                self.focus(self.workspace().focused)?;
+            }
+            self.arrange_windows()?;
+        }
+        Ok(())
+    }
+
+    /// Просит окно закрыться: окно с WM_DELETE_WINDOW успеет спросить
+    /// о несохранённом, остальные отключаются от сервера.
+    fn close_window(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        let delete = self.atoms.WM_DELETE_WINDOW;
+        if icccm::supports(&self.conn, window, &self.atoms, delete) {
+            println!("Окну {} отправлен WM_DELETE_WINDOW", window);
+            icccm::send(&self.conn, window, &self.atoms, delete, x11rb::CURRENT_TIME)
+        } else {
+            println!(
+                "Окно {} не поддерживает WM_DELETE_WINDOW, клиент отключён",
+                window
+            );
+            self.conn.kill_client(window)?;
+            Ok(())
+        }
+    }
+
+    /// Вызывает блокнот `name` на текущий монитор или прячет его, если он
+    /// уже здесь. Если окна блокнота ещё нет, запускает его команду.
+    fn toggle_scratchpad(&mut self, name: &str) -> Result<(), ReplyOrIdError> {
+        let Some(scratchpad) = self.config.scratchpads.iter().find(|sp| sp.name == name) else {
+            println!("Нет блокнота {}", name);
+            return Ok(());
+        };
+        let window = self
+            .clients
+            .iter()
+            .find(|(_, c)| c.scratchpad.as_deref() == Some(name))
+            .map(|(&w, _)| w);
+        let Some(window) = window else {
+            match spawn(&scratchpad.command) {
+                Ok(_) => println!("Запущен блокнот {}: {}", name, scratchpad.command),
+                Err(e) => println!("Не удалось запустить блокнот {}: {}", name, e),
+            }
+            return Ok(());
+        };
+
+        let selected = self.selected;
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        if !client.hidden && client.monitor == selected {
+            client.hidden = true;
+            self.unmap(window)?;
+            return self.focus(self.workspace().focused);
+        }
+        client.hidden = false;
+        client.monitor = selected;
+        self.place_scratchpad(window)?;
+        self.map(window)?;
+        self.focus(Some(window))
+    }
+
+    /// Ставит блокнот по центру его монитора поверх остальных окон,
+    /// сохраняя его размер, насколько позволяет монитор.
+    fn place_scratchpad(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        let Some(border) = self.clients.get(&window).map(|c| self.client_border(c)) else {
+            return Ok(());
+        };
+        self.center_floating(window)?;
+        self.conn.configure_window(
+            window,
+            &ConfigureWindowAux::new()
+                .border_width(border)
+                .stack_mode(StackMode::ABOVE),
+        )?;
+        Ok(())
+    }
+
+    /// Ставит плавающую геометрию окна по центру рабочей области его
+    /// монитора вместе с рамкой, уменьшая окно, если оно не помещается.
+    fn center_floating(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+        let border = 2 * self.client_border(client);
+        let area = self.monitors[client.monitor].work_area();
+        let area = Rect {
+            width: area.width.saturating_sub(border).max(1),
+            height: area.height.saturating_sub(border).max(1),
+            ..area
+        };
+        let rect = client.float_geometry.centered_in(area);
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.float_geometry = rect;
+        }
+        self.conn.configure_window(
+            window,
+            &ConfigureWindowAux::new()
+                .x(rect.x)
+                .y(rect.y)
+                .width(rect.width)
+                .height(rect.height),
+        )?;
+        Ok(())
+    }
+
+    /// Ставит новое плавающее окно туда, где приложение плавало в прошлый
+    /// раз, а если такого нет и окно не указало своё положение
+    /// в WM_NORMAL_HINTS — в центр монитора.
+    fn place_new_floating(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        if !self.floats(window) {
+            return Ok(());
+        }
+        if let Some(parent) = self.clients.get(&window).and_then(|c| c.transient_for)
+            && self.is_visible(parent)
+        {
+            return self.center_over_parent(window, parent);
+        }
+        let positioned = self
+            .clients
+            .get(&window)
+            .and_then(|c| c.size_hints)
+            .is_some_and(|hints| hints.position.is_some());
+        if !self.restore_floating(window)? && !positioned {
+            self.center_floating(window)?;
+        }
+        Ok(())
+    }
+
+    /// Ставит диалог по центру над родителем, не выпуская его за рабочую
+    /// область монитора, если он в неё помещается.
+    fn center_over_parent(&mut self, window: Window, parent: Window) -> Result<(), ReplyOrIdError> {
+        let Some(parent) = self.window_geometry(parent) else {
+            return Ok(());
+        };
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        let area = self.monitors[client.monitor].work_area();
+        let geometry = &mut client.float_geometry;
+        let (x, y) = parent.center();
+        let clamp = |start: i32, length: u32, min: i32, size: u32| {
+            start.min(min + size as i32 - length as i32).max(min)
+        };
+        geometry.x = clamp(
+            x - geometry.width as i32 / 2,
+            geometry.width,
+            area.x,
+            area.width,
+        );
+        geometry.y = clamp(
+            y - geometry.height as i32 / 2,
+            geometry.height,
+            area.y,
+            area.height,
+        );
+        let rect = *geometry;
+        self.conn.configure_window(
+            window,
+            &ConfigureWindowAux::new()
+                .x(rect.x)
+                .y(rect.y)
+                .width(rect.width)
+                .height(rect.height),
+        )?;
+        Ok(())
+    }
+
+    /// Запоминает плавающую геометрию окна для его приложения.
+    fn remember_floating(&mut self, window: Window) {
+        if !self.floats(window) {
+            return;
+        }
+        if let Some(client) = self.clients.get(&window) {
+            // У развёрнутого окна запоминается геометрия до разворачивания.
+            let rect = client
+                .unmaximized
+                .map_or(client.float_geometry, |(rect, _)| rect);
+            self.floating_memory.remember(&client.class, rect);
+        }
+    }
+
+    /// Возвращает окну запомненную для его приложения геометрию, если
+    /// она лежит на мониторе окна. Иначе окно, чья геометрия вне
+    /// монитора, центрируется. Возвращает, была ли геометрия восстановлена.
+    fn restore_floating(&mut self, window: Window) -> Result<bool, ReplyOrIdError> {
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(false);
+        };
+        let area = self.monitors[client.monitor].work_area();
+        let remembered = self
+            .floating_memory
+            .get(&client.class)
+            .filter(|rect| rect.overlap(area) > 0);
+        let Some(rect) = remembered else {
+            if client.float_geometry.overlap(area) == 0 {
+                self.center_floating(window)?;
+            }
+            return Ok(false);
+        };
+        client.float_geometry = rect;
+        self.conn.configure_window(
+            window,
+            &ConfigureWindowAux::new()
+                .x(rect.x)
+                .y(rect.y)
+                .width(rect.width)
+                .height(rect.height),
+        )?;
+        Ok(true)
+    }
+
+    /// Центрирует плавающее окно с фокусом на его мониторе.
+    fn center_focused(&mut self) -> Result<(), ReplyOrIdError> {
+        match self.workspace().focused.filter(|&w| self.floats(w)) {
+            Some(window) => self.center_floating(window),
+            None => Ok(()),
+        }
+    }
+
+    /// Запоминает сторону сфокусированного окна, куда встанет следующее
+    /// окно, и подсвечивает эту половину. Повтор той же стороны отменяет выбор.
+    fn preselect(&mut self, direction: Direction) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        if self.presel == Some((window, direction)) {
+            return self.cancel_preselect();
+        }
+        let Some(rect) = self.window_geometry(window) else {
+            return Ok(());
+        };
+        let (first, second) = halve(rect, direction.is_horizontal());
+        let hint = if direction.is_leading() {
+            first
+        } else {
+            second
+        };
+
+        self.presel = Some((window, direction));
+        self.conn.configure_window(
+            self.presel_hint,
+            &ConfigureWindowAux::new()
+                .x(hint.x)
+                .y(hint.y)
+                .width(hint.width.max(1))
+                .height(hint.height.max(1))
+                .stack_mode(StackMode::ABOVE),
+        )?;
+        self.conn.map_window(self.presel_hint)?;
+        Ok(())
+    }
+
+    fn cancel_preselect(&mut self) -> Result<(), ReplyOrIdError> {
+        self.presel = None;
+        self.conn.unmap_window(self.presel_hint)?;
+        Ok(())
+    }
+
+    fn split_focused(&mut self, horizontal: bool) {
+        if let Some(window) = self.workspace().focused {
+            let layout = if horizontal {
+                ContainerLayout::Horizontal
+            } else {
+                ContainerLayout::Vertical
+            };
+            self.workspace_mut().tree.split(window, layout);
+            println!(
+                "Окно {} будет разделено {}",
+                window,
+                if horizontal {
+                    "по горизонтали"
+                } else {
+                    "по вертикали"
+                }
+            );
+        }
+    }
+
+    /// Переключает контейнер сфокусированного окна между `layout` и делением.
+    fn toggle_container_layout(&mut self, layout: ContainerLayout) -> Result<(), ReplyOrIdError> {
+        if let Some(window) = self.workspace().focused {
+            self.workspace_mut().tree.toggle_layout(window, layout);
+            self.arrange_windows()?;
+        }
+        Ok(())
+    }
+
+    /// Листает вкладки контейнера, а в раскладке «колода» — карты стопки.
+    fn cycle_tab(&mut self, forward: bool) -> Result<(), ReplyOrIdError> {
+        if let LayoutMode::Deck = self.workspace().layout {
+            // Плавающие окна в колоду не входят, как и при её раскладке.
+            let windows: Vec<Window> = self
+                .visible_windows()
+                .into_iter()
+                .filter(|&w| !self.floats(w))
+                .collect();
+            let masters = self.workspace().params.nmaster.min(windows.len());
+            if windows.len() == masters {
+                return Ok(());
+            }
+            let len = windows.len() - masters;
+            let shown = self.workspace().params.deck_shown % len;
+            let next = if forward {
+                (shown + 1) % len
+            } else {
+                (shown + len - 1) % len
+            };
+            return self.focus(Some(windows[masters + next]));
+        }
+
+        let workspace = self.workspace_mut();
+        let next = workspace
+            .focused
+            .and_then(|window| workspace.tree.cycle_tab(window, forward));
+        if next.is_some() {
+            self.focus(next)?;
+        }
+        Ok(())
+    }
+
+    fn toggle_pseudo_tiled(&mut self) -> Result<(), ReplyOrIdError> {
+        let focused = self.workspace().focused;
+        let client = focused.and_then(|w| self.clients.get_mut(&w));
+        if let Some(client) = client {
+            client.pseudo_tiled = !client.pseudo_tiled;
+            self.arrange_windows()?;
+        }
+        Ok(())
+    }
+
+    /// Закреплённое окно получает теги всех столов монитора и видно при
+    /// любом виде; открепление оставляет его только на основном столе.
+    fn toggle_sticky(&mut self) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        client.sticky = !client.sticky;
+        let tags = if client.sticky {
+            self.monitors[client.monitor].all_tags()
+        } else {
+            1 << self.monitors[client.monitor].current
+        };
+        self.set_tags(window, tags);
+        self.update_net_wm_state(window)?;
+        self.show_hide()?;
+        self.arrange_windows()
+    }
+
+    /// Переключает плавающее состояние окна с фокусом. Окно всплывает
+    /// там, где его приложение плавало в прошлый раз, а если его геометрия
+    /// вне монитора — по центру монитора.
+    fn toggle_floating(&mut self) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        self.remember_floating(window);
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        client.floating = !client.floating;
+        if client.floating {
+            self.restore_floating(window)?;
+        }
+        self.arrange_windows()?;
+        self.focus(Some(window))
+    }
+
+    /// Сдвигает плавающее окно с фокусом на `float_step` или, при
+    /// `resize`, на столько же меняет его ширину или высоту.
+    fn nudge_floating(&mut self, direction: Direction, resize: bool) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused.filter(|&w| self.floats(w)) else {
+            return Ok(());
+        };
+        let step = self.monitor().scaled(self.config.float_step) as i32;
+        let delta = if direction.is_leading() { -step } else { step };
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        let mut rect = client.float_geometry;
+        match (resize, direction.is_horizontal()) {
+            (false, true) => rect.x += delta,
+            (false, false) => rect.y += delta,
+            (true, true) => rect.width = (rect.width as i32 + delta).max(1) as u32,
+            (true, false) => rect.height = (rect.height as i32 + delta).max(1) as u32,
+        }
+        (rect.width, rect.height) = client.constrain(rect.width, rect.height);
+        client.float_geometry = rect;
+        self.conn.configure_window(
+            window,
+            &ConfigureWindowAux::new()
+                .x(rect.x)
+                .y(rect.y)
+                .width(rect.width)
+                .height(rect.height),
+        )?;
+        self.remember_floating(window);
+        self.follow_floating_monitor(window)
+    }
+
+    /// Плавает ли окно: само по себе или потому, что стол его монитора
+    /// в плавающем режиме.
+    fn floats(&self, window: Window) -> bool {
+        self.clients.get(&window).is_some_and(|client| {
+            client.floating
+                || matches!(
+                    self.monitors[client.monitor].workspace().layout,
+                    LayoutMode::Floating
+                )
+        })
+    }
+
+    /// Выстраивает стопку монитора по слоям снизу вверх: рабочий стол,
+    /// тайловые окна, плавающие, окна на весь экран, окна «поверх всех»,
+    /// панели и блокноты. Диалоги лежат выше остальных окон своего слоя,
+    /// а в остальном окна слоя идут в порядке `stack`. Чужие окна вроде
+    /// меню остаются над стопкой. Новый порядок стопки публикуется.
+    fn restack(&self, index: usize) -> Result<(), ReplyOrIdError> {
+        let position = |w: Window| self.stack.iter().position(|&s| s == w);
+        let mut windows = self.monitors[index].visible_windows();
+        windows.sort_by_key(|&w| {
+            let layer = self.clients.get(&w).map_or(0, |c| {
+                if c.above {
+                    3
+                } else if c.fullscreen {
+                    2
+                } else if self.floats(w) {
+                    1
+                } else {
+                    0
+                }
+            });
+            let dialog = self
+                .clients
+                .get(&w)
+                .is_some_and(|c| c.transient_for.is_some());
+            (layer, dialog, position(w))
+        });
+        let windows = self
+            .desktop_windows
+            .iter()
+            .copied()
+            .chain(windows)
+            .chain(self.docks.iter().copied())
+            .chain(self.clients.iter().filter_map(|(&w, c)| {
+                (c.scratchpad.is_some() && c.monitor == index && self.shows(c)).then_some(w)
+            }));
+        let mut below = None;
+        for window in windows {
+            let aux = match below {
+                None => ConfigureWindowAux::new().stack_mode(StackMode::BELOW),
+                Some(sibling) => ConfigureWindowAux::new()
+                    .sibling(sibling)
+                    .stack_mode(StackMode::ABOVE),
+            };
+            self.conn.configure_window(window, &aux)?;
+            below = Some(window);
+        }
+        self.update_client_list()
+    }
+
+    /// Поднимает окно в пределах его слоя или опускает его.
+    fn raise(&mut self, window: Window, up: bool) {
+        if !self.clients.contains_key(&window) {
+            return;
+        }
+        self.stack.retain(|&w| w != window);
+        if up {
+            self.stack.push(window);
+        } else {
+            self.stack.insert(0, window);
+        }
+    }
+
+    /// Просьба окна или пейджера поднять окно или опустить; соседа по
+    /// стопке менеджер выбирает сам, а Opposite считается подъёмом.
+    fn handle_stack_request(
+        &mut self,
+        window: Window,
+        mode: StackMode,
+    ) -> Result<(), ReplyOrIdError> {
+        let Some(monitor) = self.clients.get(&window).map(|c| c.monitor) else {
+            return Ok(());
+        };
+        let up = !matches!(mode, StackMode::BELOW | StackMode::BOTTOM_IF);
+        self.raise(window, up);
+        self.restack(monitor)
+    }
+
+    /// Публикует списки управляемых окон; порядок стопки берётся у сервера.
+    fn update_client_list(&self) -> Result<(), ReplyOrIdError> {
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let stacking: Vec<Window> = self
+            .conn
+            .query_tree(root)?
+            .reply()?
+            .children
+            .into_iter()
+            .filter(|w| self.clients.contains_key(w))
+            .collect();
+        ewmh::set_client_lists(&self.conn, root, &self.atoms, &self.client_list, &stacking)
+    }
+
+    /// Переключает разворачивание окна с фокусом. Для обеих осей сразу
+    /// полностью развёрнутое окно сворачивается, иначе разворачивается.
+    fn toggle_maximize(&mut self, horizontal: bool, vertical: bool) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+        let (h, v) = client.maximized;
+        let wanted = match (horizontal, vertical) {
+            (true, true) => (!(h && v), !(h && v)),
+            (true, false) => (!h, v),
+            (false, _) => (h, !v),
+        };
+        self.set_maximized(window, wanted)
+    }
+
+    /// Разворачивает окно по осям на рабочую область монитора, оставляя
+    /// рамку. Развёрнутое окно плавает; когда обе оси свёрнуты, окно
+    /// возвращается к прежней геометрии и, если было тайловым, в тайлинг.
+    fn set_maximized(
+        &mut self,
+        window: Window,
+        (horizontal, vertical): (bool, bool),
+    ) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+        let border = 2 * self.client_border(client);
+        let area = self.monitors[client.monitor].work_area();
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        let was = client.maximized;
+        if was == (horizontal, vertical) {
+            return Ok(());
+        }
+        let (saved, floating) = *client
+            .unmaximized
+            .get_or_insert((client.float_geometry, client.floating));
+        let rect = &mut client.float_geometry;
+        if horizontal {
+            rect.x = area.x;
+            rect.width = area.width.saturating_sub(border).max(1);
+        } else if was.0 {
+            rect.x = saved.x;
+            rect.width = saved.width;
+        }
+        if vertical {
+            rect.y = area.y;
+            rect.height = area.height.saturating_sub(border).max(1);
+        } else if was.1 {
+            rect.y = saved.y;
+            rect.height = saved.height;
+        }
+        client.maximized = (horizontal, vertical);
+        if horizontal || vertical {
+            client.floating = true;
+        } else {
+            client.floating = floating;
+            client.unmaximized = None;
+        }
+        self.update_net_wm_state(window)?;
+        self.arrange_windows()
+    }
+
+    /// Включает или снимает для окна удержание над остальными.
+    fn set_above(&mut self, window: Window, above: bool) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        client.above = above;
+        let monitor = client.monitor;
+        self.update_net_wm_state(window)?;
+        self.restack(monitor)
+    }
+
+    /// Разворачивает окно на весь монитор поверх панели и без рамки или
+    /// возвращает его на прежнее место.
+    fn set_fullscreen(&mut self, window: Window, fullscreen: bool) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        if client.fullscreen == fullscreen {
+            return Ok(());
+        }
+        client.fullscreen = fullscreen;
+        self.update_net_wm_state(window)?;
+        self.arrange_windows()
+    }
+
+    /// Публикует состояния окна в `_NET_WM_STATE`.
+    fn update_net_wm_state(&self, window: Window) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+        let mut states = Vec::new();
+        if client.sticky {
+            states.push(self.atoms._NET_WM_STATE_STICKY);
+        }
+        if client.above {
+            states.push(self.atoms._NET_WM_STATE_ABOVE);
+        }
+        if client.fullscreen {
+            states.push(self.atoms._NET_WM_STATE_FULLSCREEN);
+        }
+        if client.urgent {
+            states.push(self.atoms._NET_WM_STATE_DEMANDS_ATTENTION);
+        }
+        if client.maximized.0 {
+            states.push(self.atoms._NET_WM_STATE_MAXIMIZED_HORZ);
+        }
+        if client.maximized.1 {
+            states.push(self.atoms._NET_WM_STATE_MAXIMIZED_VERT);
+        }
+        ewmh::set_wm_state(&self.conn, window, &self.atoms, &states)
+    }
+
+    /// Меняет долю мастер-области на `steps` шагов из настроек.
+    /// В древесных раскладках вместо этого меняется доля
+    /// сфокусированного окна в его контейнере.
+    fn adjust_master(&mut self, steps: f32) -> Result<(), ReplyOrIdError> {
+        let delta = steps * self.config.master_factor_step;
+        let workspace = self.workspace_mut();
+        if let LayoutMode::Bsp | LayoutMode::Tree = workspace.layout {
+            if let Some(window) = workspace.focused {
+                workspace.tree.resize(window, delta);
+            }
+            return self.arrange_windows();
+        }
+        let params = &mut workspace.params;
+        params.master_factor = (params.master_factor + delta)
+            .clamp(*MASTER_FACTOR_RANGE.start(), *MASTER_FACTOR_RANGE.end());
+        println!("Доля мастер-области: {:.2}", params.master_factor);
+        self.arrange_windows()
+    }
+
+    fn adjust_master_count(&mut self, delta: isize) -> Result<(), ReplyOrIdError> {
+        let params = &mut self.workspace_mut().params;
+        params.nmaster = params.nmaster.saturating_add_signed(delta);
+        println!("Окон в мастер-области: {}", params.nmaster);
+        self.arrange_windows()
+    }
+
+    fn balance(&mut self) -> Result<(), ReplyOrIdError> {
+        let workspace = self.workspace_mut();
+        if let Some(window) = workspace.focused {
+            workspace.tree.balance(window);
+            self.arrange_windows()?;
+        }
+        Ok(())
+    }
+
+    fn toggle_transform(&mut self, rotate: bool) -> Result<(), ReplyOrIdError> {
+        let transform = &mut self.workspace_mut().transform;
+        if rotate {
+            transform.rotated = !transform.rotated;
+        } else {
+            transform.mirrored = !transform.mirrored;
+        }
+        self.arrange_windows()
+    }
+
+    fn toggle_layout(&mut self) -> Result<(), ReplyOrIdError> {
+        let workspace = self.workspace_mut();
+        workspace.layout = match workspace.layout {
+            LayoutMode::Horizontal => LayoutMode::Vertical,
+            LayoutMode::Vertical => LayoutMode::Monocle,
+            LayoutMode::Monocle => LayoutMode::Grid,
+            LayoutMode::Grid => LayoutMode::Spiral,
+            LayoutMode::Spiral => LayoutMode::Dwindle,
+            LayoutMode::Dwindle => LayoutMode::Deck,
+            LayoutMode::Deck => LayoutMode::ThreeColumn,
+            LayoutMode::ThreeColumn => LayoutMode::CenteredMaster,
+            LayoutMode::CenteredMaster => LayoutMode::Floating,
+            LayoutMode::Floating => LayoutMode::Bsp,
+            LayoutMode::Bsp => LayoutMode::Tree,
+            LayoutMode::Tree => LayoutMode::Horizontal,
+        };
+        self.arrange_windows()?;
+        Ok(())
+    }
+
+    /// Отдаёт ввод окну (или корневому окну) и публикует его в
+    /// `_NET_ACTIVE_WINDOW`. Окну, отказавшемуся от ввода в WM_HINTS,
+    /// фокус не ставится; окну с WM_TAKE_FOCUS отправляется сообщение,
+    /// и фокус оно берёт само. Сообщение помечено `user_time`: клиенты на
+    /// Java игнорируют WM_TAKE_FOCUS с CurrentTime. Сам фокус ставится с
+    /// CurrentTime: запрос со временем раньше последней смены фокуса
+    /// сервер отбросил бы. При фокусе щелчком несфокусированные окна
+    /// держат захват первой кнопки, а у окна с фокусом он снимается,
+    /// чтобы щелчки доходили до него напрямую.
+    fn set_input_focus(&mut self, window: Option<Window>) -> Result<(), ReplyOrIdError> {
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let (input, take_focus) = window
+            .and_then(|w| self.clients.get(&w))
+            .map_or((true, false), |c| (c.input, c.take_focus));
+        if input {
+            self.conn.set_input_focus(
+                InputFocus::POINTER_ROOT,
+                window.unwrap_or(root),
+                x11rb::CURRENT_TIME,
+            )?;
+        }
+        if let Some(window) = window.filter(|_| take_focus) {
+            let protocol = self.atoms.WM_TAKE_FOCUS;
+            icccm::send(&self.conn, window, &self.atoms, protocol, self.user_time)?;
+        }
+        ewmh::set_active_window(&self.conn, root, &self.atoms, window)?;
+        let previous = std::mem::replace(&mut self.input_focus, window);
+        if let Some(window) = window {
+            self.set_urgent(window, false)?;
+        }
+        if self.config.focus_follows_mouse || previous == window {
+            return Ok(());
+        }
+        if let Some(previous) = previous.filter(|w| self.clients.contains_key(w)) {
+            self.grab_buttons(previous, false)?;
+        }
+        if let Some(window) = window {
+            self.grab_buttons(window, true)?;
+        }
+        Ok(())
+    }
+
+    /// Захватывает кнопки на окне: Mod4+Button1 для перетаскивания и
+    /// Mod4+Button3 для изменения размера, а при
+    /// фокусе щелчком у окна без фокуса — любой щелчок первой кнопкой;
+    /// такое нажатие затем переигрывается приложению. Захват с конкретным
+    /// модификатором ставится после общего, чтобы иметь приоритет.
+    fn grab_buttons(&self, window: Window, focused: bool) -> Result<(), ReplyOrIdError> {
+        self.conn
+            .ungrab_button(ButtonIndex::ANY, window, ModMask::ANY)?;
+        if !focused && !self.config.focus_follows_mouse {
+            self.conn.grab_button(
+                false,
+                window,
+                EventMask::BUTTON_PRESS,
+                GrabMode::SYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                x11rb::NONE,
+                ButtonIndex::M1,
+                ModMask::ANY,
+            )?;
+        }
+        for button in [ButtonIndex::M1, ButtonIndex::M3] {
+            for ignored in ignored_modifiers() {
+                self.conn.grab_button(
+                    false,
+                    window,
+                    EventMask::BUTTON_PRESS,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                    x11rb::NONE,
+                    x11rb::NONE,
+                    button,
+                    self.config.modifier.mask() | ignored,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Начинает перетаскивание окна по нажатию Mod4 с кнопкой мыши. При
+    /// `resize` тянется ближайший к указателю угол. Тайловые окна только
+    /// получают фокус.
+    fn start_drag(&mut self, event: &ButtonPressEvent, resize: bool) -> Result<(), ReplyOrIdError> {
+        let window = event.event;
+        self.focus(Some(window))?;
+        let Some(geometry) = self.clients.get(&window).map(|c| c.float_geometry) else {
+            return Ok(());
+        };
+        let corner = resize.then_some((
+            i32::from(event.event_x) < geometry.width as i32 / 2,
+            i32::from(event.event_y) < geometry.height as i32 / 2,
+        ));
+        let pointer = (i32::from(event.root_x), i32::from(event.root_y));
+        self.begin_drag(window, pointer, corner, (true, true))
+    }
+
+    /// Захватывает указатель и начинает перетаскивание плавающего окна
+    /// от точки `pointer`: перемещение или, если задан угол, изменение
+    /// размера по осям `axes`.
+    fn begin_drag(
+        &mut self,
+        window: Window,
+        pointer: (i32, i32),
+        corner: Option<(bool, bool)>,
+        axes: (bool, bool),
+    ) -> Result<(), ReplyOrIdError> {
+        if self.drag.is_some() || !self.floats(window) {
+            return Ok(());
+        }
+        let Some(geometry) = self.clients.get(&window).map(|c| c.float_geometry) else {
+            return Ok(());
+        };
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let grab = self
+            .conn
+            .grab_pointer(
+                false,
+                root,
+                EventMask::BUTTON_RELEASE | EventMask::BUTTON_MOTION,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                x11rb::NONE,
+                x11rb::CURRENT_TIME,
+            )?
+            .reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            return Ok(());
+        }
+        let edges = if corner.is_some() {
+            (Vec::new(), Vec::new())
+        } else {
+            self.snap_edges(window)
+        };
+        self.drag = Some(Drag {
+            window,
+            pointer,
+            geometry,
+            corner,
+            axes,
+            edges,
+        });
+        Ok(())
+    }
+
+    /// Края мониторов, их рабочих областей (без панели) и видимых окон,
+    /// кроме `window`, к которым прилипает перетаскиваемое окно.
+    fn snap_edges(&self, window: Window) -> (Vec<i32>, Vec<i32>) {
+        let mut rects: Vec<Rect> = self
+            .monitors
+            .iter()
+            .flat_map(|m| [m.area, m.work_area()])
+            .collect();
+        for (&other, client) in &self.clients {
+            if other == window || !self.shows(client) {
+                continue;
+            }
+            let Some(rect) = self.window_geometry(other) else {
+                continue;
+            };
+            let border = 2 * self.client_border(client);
+            let rect = Rect {
+                width: rect.width + border,
+                height: rect.height + border,
+                ..rect
+            };
+            // Неактивные вкладки уведены за пределы экрана.
+            if self.monitors.iter().any(|m| m.area.overlap(rect) > 0) {
+                rects.push(rect);
+            }
+        }
+        let xs = rects
+            .iter()
+            .flat_map(|r| [r.x, r.x + r.width as i32])
+            .collect();
+        let ys = rects
+            .iter()
+            .flat_map(|r| [r.y, r.y + r.height as i32])
+            .collect();
+        (xs, ys)
+    }
+
+    /// Сдвигает перетаскиваемое окно или его угол вслед за указателем.
+    fn handle_drag_motion(&mut self, event: &MotionNotifyEvent) -> Result<(), ReplyOrIdError> {
+        let Some(drag) = &self.drag else {
+            return Ok(());
+        };
+        let window = drag.window;
+        let (dx, dy) = (
+            i32::from(event.root_x) - drag.pointer.0,
+            i32::from(event.root_y) - drag.pointer.1,
+        );
+        let start = drag.geometry;
+        let geometry = match drag.corner {
+            None => {
+                let (x, y) = (start.x + dx, start.y + dy);
+                let client = self.clients.get(&window);
+                let border = client.map_or(0, |c| 2 * self.client_border(c)) as i32;
+                let (width, height) = (start.width as i32 + border, start.height as i32 + border);
+                let threshold = self.config.snap_distance;
+                Rect {
+                    x: x + snap(x, x + width, &drag.edges.0, threshold),
+                    y: y + snap(y, y + height, &drag.edges.1, threshold),
+                    ..start
+                }
+            }
+            Some((left, top)) => {
+                let (dx, dy) = (dx * i32::from(drag.axes.0), dy * i32::from(drag.axes.1));
+                let (x, width) = resize_edge(start.x, start.width, dx, left);
+                let (y, height) = resize_edge(start.y, start.height, dy, top);
+                let (width, height) = self
+                    .clients
+                    .get(&window)
+                    .map_or((width, height), |c| c.constrain(width, height));
+                // Упёршийся в ограничение левый или верхний край не
+                // должен тащить окно за собой.
+                Rect {
+                    x: if left {
+                        start.x + start.width as i32 - width as i32
+                    } else {
+                        x
+                    },
+                    y: if top {
+                        start.y + start.height as i32 - height as i32
+                    } else {
+                        y
+                    },
+                    width,
+                    height,
+                }
+            }
+        };
+        let Some(client) = self.clients.get_mut(&window) else {
+            return Ok(());
+        };
+        client.float_geometry = geometry;
+        self.conn.configure_window(
+            window,
+            &ConfigureWindowAux::new()
+                .x(geometry.x)
+                .y(geometry.y)
+                .width(geometry.width)
+                .height(geometry.height),
+        )?;
+        Ok(())
+    }
+
+    /// Завершает перетаскивание; окно переходит к монитору, на который
+    /// его перенесли.
+    fn finish_drag(&mut self) -> Result<(), ReplyOrIdError> {
+        let Some(drag) = self.drag.take() else {
+            return Ok(());
+        };
+        self.conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+        self.remember_floating(drag.window);
+        self.follow_floating_monitor(drag.window)
+    }
+
+    /// Фокусирует окно. Блокнот получает ввод и поднимается, но фокус
+    /// стола не меняет: после его скрытия фокус вернётся туда, где был.
+    fn focus(&mut self, window: Option<u32>) -> Result<(), ReplyOrIdError> {
+        if let Some(window) = window
+            && self
+                .clients
+                .get(&window)
+                .is_some_and(|c| c.scratchpad.is_some())
+        {
+            self.conn.configure_window(
+                window,
+                &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+            )?;
+            return self.set_input_focus(Some(window));
+        }
+        // Фокус на окне другого монитора делает этот монитор текущим.
+        if let Some(monitor) = window.and_then(|w| self.clients.get(&w)).map(|c| c.monitor) {
+            self.selected = monitor;
+        }
+        let workspace = self.workspace_mut();
+        workspace.focused = window;
+        if let Some(window) = window {
+            workspace.tree.set_active(window);
+            if self.mru_cycle.is_none() {
+                self.touch(window);
+            }
+        }
+        if let Some(window) = window {
+            self.raise(window, true);
+        }
+        self.set_input_focus(window)?;
+        self.update_autonames()?;
+        self.update_bars()?;
+        match (self.workspace().layout, window) {
+            // Фокус мог переключить вкладку или её подсветку.
+            (LayoutMode::Bsp | LayoutMode::Tree, _) => self.arrange_windows()?,
+            (LayoutMode::Deck, Some(window)) => {
+                let pos = self
+                    .visible_windows()
+                    .into_iter()
+                    .filter(|&w| !self.floats(w))
+                    .position(|w| w == window);
+                let params = &mut self.workspace_mut().params;
+                let nmaster = params.nmaster;
+                if let Some(pos) = pos.filter(|&pos| pos >= nmaster) {
+                    params.deck_shown = pos - nmaster;
+                    self.arrange_windows()?;
+                }
+            }
+            _ => {}
+        }
+        self.restack(self.selected)
+    }
+
+    /// Показывает только стол `index`: окна остальных снимаются с экрана,
+    /// фокус возвращается туда, где был. Повторный выбор текущего стола
+    /// при `auto_back_and_forth` возвращает на предыдущий.
+    fn view_workspace(&mut self, index: usize) -> Result<(), ReplyOrIdError> {
+        let monitor = self.monitor();
+        let index = if index == monitor.current && self.config.auto_back_and_forth {
+            monitor.previous
+        } else {
+            index
+        };
+        if index >= monitor.workspaces.len() || monitor.view == 1 << index {
+            return Ok(());
+        }
+        if self.presel.is_some() {
+            self.cancel_preselect()?;
+        }
+        let monitor = self.monitor_mut();
+        if index != monitor.current {
+            monitor.previous = monitor.current;
+            monitor.current = index;
+        }
+        monitor.view = 1 << index;
+        println!("Рабочий стол {}", self.workspace().label(index));
+        self.collect_workspaces()?;
+        self.show_hide()?;
+        self.arrange_windows()?;
+        self.focus(self.workspace().focused)
+    }
+
+    fn collect_workspaces(&mut self) -> Result<(), ReplyOrIdError> {
+        let config = &self.config;
+        if self.monitors[self.selected].collect_workspaces(config) {
+            self.update_desktops()?;
+        }
+        Ok(())
+    }
+
+    /// Публикует для панелей и пейджеров число столов, их имена и рабочие
+    /// области; столы всех мониторов идут подряд.
+    fn update_desktops(&self) -> Result<(), ReplyOrIdError> {
+        let names: Vec<String> = self
+            .monitors
+            .iter()
+            .flat_map(|monitor| monitor.workspaces.iter().enumerate())
+            .map(|(i, ws)| ws.label(i))
+            .collect();
+        let areas: Vec<Rect> = self
+            .monitors
+            .iter()
+            .flat_map(|monitor| monitor.workspaces.iter().map(|_| monitor.work_area()))
+            .collect();
+        let root = self.conn.setup().roots[self.screen_num].root;
+        ewmh::set_desktop_names(&self.conn, root, &self.atoms, &names)?;
+        ewmh::set_work_areas(&self.conn, root, &self.atoms, &areas)
+    }
+
+    /// Публикует текущий стол монитора с фокусом, если он сменился.
+    fn update_current_desktop(&mut self) -> Result<(), ReplyOrIdError> {
+        let desktop = self.desktop_offset(self.selected) + self.monitor().current;
+        if self.current_desktop == Some(desktop) {
+            return Ok(());
+        }
+        self.current_desktop = Some(desktop);
+        let root = self.conn.setup().roots[self.screen_num].root;
+        ewmh::set_current_desktop(&self.conn, root, &self.atoms, desktop)
+    }
+
+    /// Публикует `_NET_WM_DESKTOP` окон, чей стол сменился: первый тег
+    /// окна в сквозной нумерации, а для закреплённых окон и блокнотов —
+    /// все столы.
+    fn update_window_desktops(&mut self) -> Result<(), ReplyOrIdError> {
+        let offsets: Vec<usize> = (0..self.monitors.len())
+            .map(|index| self.desktop_offset(index))
+            .collect();
+        for (&window, client) in &mut self.clients {
+            let desktop = if client.sticky || client.scratchpad.is_some() {
+                ewmh::ALL_DESKTOPS
+            } else {
+                (offsets[client.monitor] + client.tags.trailing_zeros() as usize) as u32
+            };
+            if client.desktop != Some(desktop) {
+                client.desktop = Some(desktop);
+                ewmh::set_window_desktop(&self.conn, window, &self.atoms, desktop)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Монитор и стол на нём по сквозному номеру стола EWMH.
+    fn desktop_position(&self, desktop: usize) -> Option<(usize, usize)> {
+        let index = (0..self.monitors.len())
+            .rev()
+            .find(|&index| self.desktop_offset(index) <= desktop)?;
+        let workspace = desktop - self.desktop_offset(index);
+        (workspace < self.monitors[index].workspaces.len()).then_some((index, workspace))
+    }
+
+    /// Номер первого стола монитора `index` в сквозной нумерации EWMH.
+    fn desktop_offset(&self, index: usize) -> usize {
+        self.monitors[..index]
+            .iter()
+            .map(|monitor| monitor.workspaces.len())
+            .sum()
+    }
+
+    /// Переходит на стол со сквозным номером EWMH, переключая монитор.
+    fn view_desktop(&mut self, desktop: usize) -> Result<(), ReplyOrIdError> {
+        let Some((index, workspace)) = self.desktop_position(desktop) else {
+            return Ok(());
+        };
+        self.select_monitor(index)?;
+        self.view_workspace(workspace)
+    }
+
+    /// Переносит окно на стол со сквозным номером EWMH, при необходимости
+    /// на другой монитор; `ALL_DESKTOPS` закрепляет окно. Блокноты живут
+    /// вне столов и не переносятся.
+    fn move_to_desktop(&mut self, window: Window, desktop: u32) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+        if client.scratchpad.is_some() {
+            return Ok(());
+        }
+        let from = client.monitor;
+        let (sticky, index, tags) = if desktop == ewmh::ALL_DESKTOPS {
+            (true, from, self.monitors[from].all_tags())
+        } else {
+            let Some((index, workspace)) = self.desktop_position(desktop as usize) else {
+                return Ok(());
+            };
+            (false, index, 1 << workspace)
+        };
+        if self.presel.is_some_and(|(w, _)| w == window) {
+            self.cancel_preselect()?;
+        }
+        if index != from {
+            let (from, to) = (self.monitors[from].area, self.monitors[index].area);
+            if let Some(client) = self.clients.get_mut(&window) {
+                client.float_geometry.x += to.x - from.x;
+                client.float_geometry.y += to.y - from.y;
+            }
+            self.transfer_to_monitor(window, index);
+        }
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.sticky = sticky;
+        }
+        self.set_tags(window, tags);
+        self.update_net_wm_state(window)?;
+        self.show_hide()?;
+        self.arrange_windows()?;
+        self.focus(self.workspace().focused)
+    }
+
+    /// Обновляет автоимена столов по классу их сфокусированного
+    /// (или первого) окна и публикует имена, если что-то изменилось.
+    fn update_autonames(&mut self) -> Result<(), ReplyOrIdError> {
+        if !self.config.autoname_workspaces {
+            return Ok(());
+        }
+        let mut changed = false;
+        for monitor in &mut self.monitors {
+            for workspace in &mut monitor.workspaces {
+                let app = workspace
+                    .focused
+                    .filter(|&w| workspace.tree.contains(w))
+                    .or_else(|| workspace.tree.windows().first().copied())
+                    .and_then(|w| self.clients.get(&w))
+                    .map(|client| client.class.clone())
+                    .filter(|class| !class.is_empty());
+                if workspace.app != app {
+                    workspace.app = app;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.update_desktops()?;
+        }
+        Ok(())
+    }
+
+    fn rename_workspace(&mut self, name: String) -> Result<(), ReplyOrIdError> {
+        let current = self.monitor().current;
+        let taken = self
+            .monitor()
+            .workspaces
+            .iter()
+            .enumerate()
+            .any(|(i, ws)| i != current && ws.name.as_deref() == Some(name.as_str()));
+        if taken {
+            println!("Имя {} уже занято", name);
+            return Ok(());
+        }
+        println!(
+            "Стол {} переименован в {}",
+            self.workspace().label(current),
+            name
+        );
+        self.workspace_mut().name = Some(name);
+        self.update_desktops()?;
+        self.update_bars()
+    }
+
+    /// Добавляет стол `index` к видимым или убирает его; последний
+    /// видимый стол убрать нельзя. Если убран основной стол, основным
+    /// становится первый из оставшихся.
+    fn toggle_view(&mut self, index: usize) -> Result<(), ReplyOrIdError> {
+        let view = self.monitor().view ^ (1 << index);
+        if index >= self.monitor().workspaces.len() || view == 0 {
+            return Ok(());
+        }
+        if self.presel.is_some() {
+            self.cancel_preselect()?;
+        }
+        let monitor = self.monitor_mut();
+        monitor.view = view;
+        if view & (1 << monitor.current) == 0 {
+            monitor.current = view.trailing_zeros() as usize;
+        }
+        self.collect_workspaces()?;
+        self.show_hide()?;
+        self.arrange_windows()?;
+        let focused = self
+            .workspace()
+            .focused
+            .filter(|&w| self.is_visible(w))
+            .or_else(|| self.visible_windows().first().copied());
+        self.focus(focused)
+    }
+
+    /// Показывает все столы; основной стол не меняется.
+    fn view_all(&mut self) -> Result<(), ReplyOrIdError> {
+        let all = self.monitor().all_tags();
+        if self.monitor().view == all {
+            return Ok(());
+        }
+        if self.presel.is_some() {
+            self.cancel_preselect()?;
+        }
+        self.monitor_mut().view = all;
+        self.show_hide()?;
+        self.arrange_windows()?;
+        self.focus(self.workspace().focused)
+    }
+
+    /// Назначает окну маску тегов и приводит к ней деревья столов
+    /// его монитора.
+    fn set_tags(&mut self, window: Window, tags: u32) {
+        let Some(monitor) = self
+            .clients
+            .get(&window)
+            .map(|c| &mut self.monitors[c.monitor])
+        else {
+            return;
+        };
+        let area = monitor.work_area();
+        for (i, workspace) in monitor.workspaces.iter_mut().enumerate() {
+            let tagged = tags & (1 << i) != 0;
+            match (tagged, workspace.tree.contains(window)) {
+                (true, false) => workspace.insert(window, area),
+                (false, true) => workspace.remove(window),
+                _ => {}
+            }
+        }
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.tags = tags;
+        }
+    }
+
+    /// Добавляет сфокусированному окну тег стола `index` или снимает его;
+    /// последний тег снять нельзя.
+    fn toggle_tag(&mut self, index: usize) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        let Some(tags) = self.clients.get(&window).map(|c| c.tags ^ (1 << index)) else {
+            return Ok(());
+        };
+        if index >= self.monitor().workspaces.len() || tags == 0 {
+            return Ok(());
+        }
+        self.set_tags(window, tags);
+        self.show_hide()?;
+        self.arrange_windows()?;
+        self.focus(self.workspace().focused)
+    }
+
+    /// Переносит сфокусированное окно на стол `index` и перестраивает оба
+    /// стола. При `follow` вслед за окном переключается и вид.
+    fn move_to_workspace(&mut self, index: usize, follow: bool) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        if index >= self.monitor().workspaces.len() {
+            return Ok(());
+        }
+        if self.presel.is_some_and(|(w, _)| w == window) {
+            self.cancel_preselect()?;
+        }
+
+        if let Some(client) = self.clients.get_mut(&window)
+            && client.sticky
+        {
+            client.sticky = false;
+            self.update_net_wm_state(window)?;
+        }
+        self.set_tags(window, 1 << index);
+        self.monitor_mut().workspaces[index].focused = Some(window);
+
+        if follow {
+            return self.view_workspace(index);
+        }
+        self.show_hide()?;
+        self.arrange_windows()?;
+        self.focus(self.workspace().focused)
+    }
+
+    /// Переходит на соседний стол монитора по кругу.
+    fn cycle_workspace(&mut self, forward: bool) -> Result<(), ReplyOrIdError> {
+        let monitor = self.monitor();
+        let len = monitor.workspaces.len();
+        let next = if forward {
+            (monitor.current + 1) % len
+        } else {
+            (monitor.current + len - 1) % len
+        };
+        self.view_workspace(next)
+    }
+
+    /// Фокус следует за указателем, если так настроено. Входы из-за
+    /// захватов и из дочерних окон пропускаются.
+    fn handle_enter_notify(&mut self, event: &EnterNotifyEvent) -> Result<(), ReplyOrIdError> {
+        if !self.config.focus_follows_mouse
+            || self.overview.is_some()
+            || event.mode != NotifyMode::NORMAL
+            || event.detail == NotifyDetail::INFERIOR
+            || !self.is_visible(event.event)
+        {
+            return Ok(());
+        }
+        self.end_mru_cycle();
+        let focused = self.clients.get(&event.event).map(|c| {
+            c.scratchpad.is_none()
+                && self.monitors[c.monitor].workspace().focused == Some(event.event)
+        });
+        if focused == Some(true) {
+            return Ok(());
+        }
+        self.focus(Some(event.event))
+    }
+
+    /// Нажатия кнопок: выбор ячейки обзора, щелчок по окну при фокусе
+    /// щелчком или прокрутка над пустым корневым окном (кнопки 4 и 5).
+    fn handle_button_press(&mut self, event: &ButtonPressEvent) -> Result<(), ReplyOrIdError> {
+        self.end_mru_cycle();
+        let state = u16::from(event.state);
+        if self.clients.contains_key(&event.event)
+            && matches!(event.detail, 1 | 3)
+            && state & u16::from(self.config.modifier.mask()) != 0
+        {
+            return self.start_drag(event, event.detail == 3);
+        }
+        if self.clients.contains_key(&event.event) {
+            self.focus(Some(event.event))?;
+            self.conn
+                .allow_events(Allow::REPLAY_POINTER, x11rb::CURRENT_TIME)?;
+            return Ok(());
+        }
+        if let Some(overview) = &self.overview {
+            let cell = overview.cell_at(event.event);
+            if cell.is_some() {
+                self.close_overview(cell)?;
+            }
+            return Ok(());
+        }
+        let root = self.conn.setup().roots[self.screen_num].root;
+        if event.event != root || event.child != x11rb::NONE {
+            return Ok(());
+        }
+        match event.detail {
+            4 => self.cycle_workspace(false),
+            5 => self.cycle_workspace(true),
+            _ => Ok(()),
+        }
+    }
+
+    /// Открывает обзор столов текущего монитора; повторный вызов закрывает его.
+    fn toggle_overview(&mut self) -> Result<(), ReplyOrIdError> {
+        if self.overview.is_some() {
+            return self.close_overview(None);
+        }
+        if self.presel.is_some() {
+            self.cancel_preselect()?;
+        }
+        let monitor = self.monitor();
+        let cells = monitor
+            .workspaces
+            .iter()
+            .enumerate()
+            .map(|(i, ws)| (ws.label(i), ws.tree.windows().len()))
+            .collect();
+        let screen = &self.conn.setup().roots[self.screen_num];
+        let overview = Overview::open(
+            &self.conn,
+            screen,
+            monitor.area,
+            cells,
+            monitor.current,
+            self.config.colors,
+        )?;
+        self.overview = Some(overview);
+        Ok(())
+    }
+
+    /// Закрывает обзор и переходит на выбранный стол, если он выбран.
+    fn close_overview(&mut self, pick: Option<usize>) -> Result<(), ReplyOrIdError> {
+        if let Some(overview) = self.overview.take() {
+            overview.close(&self.conn)?;
+        }
+        match pick {
+            Some(index) if index != self.monitor().current => self.view_workspace(index),
+            _ => Ok(()),
+        }
+    }
+
+    /// Клавиши обзора: стрелки и h/j/k/l двигают выделение, Return
+    /// выбирает стол, цифры выбирают стол сразу, Escape закрывает обзор.
+    fn handle_overview_key(&mut self, event: &KeyPressEvent) -> Result<(), ReplyOrIdError> {
+        let Some(overview) = &mut self.overview else {
+            return Ok(());
+        };
+        let Some(keysym) = self.keymap.keysym(event.detail) else {
+            return Ok(());
+        };
+        match keysym {
+            XK_LEFT => overview.move_selection(&self.conn, -1, 0),
+            XK_RIGHT => overview.move_selection(&self.conn, 1, 0),
+            XK_UP => overview.move_selection(&self.conn, 0, -1),
+            XK_DOWN => overview.move_selection(&self.conn, 0, 1),
+            _ if keysym == key('h') => overview.move_selection(&self.conn, -1, 0),
+            _ if keysym == key('l') => overview.move_selection(&self.conn, 1, 0),
+            _ if keysym == key('k') => overview.move_selection(&self.conn, 0, -1),
+            _ if keysym == key('j') => overview.move_selection(&self.conn, 0, 1),
+            XK_RETURN => {
+                let selected = overview.selected();
+                self.close_overview(Some(selected))
+            }
+            _ if (key('1')..=key('9')).contains(&keysym) => {
+                self.close_overview(Some((keysym - key('1')) as usize))
+            }
+            _ if keysym == XK_ESCAPE || keysym == key('o') => self.close_overview(None),
+            _ => Ok(()),
+        }
+    }
 
-impl<C: Connection> WindowManager<C> {
-    fn new(conn: C, screen_num: usize) -> Self {
-        WindowManager {
-            conn,
-            screen_num,
-            layout: LayoutMode::Horizontal,
-            windows: Vec::new(),
+    /// Индекс первого пустого стола монитора. Закреплённые окна не
+    /// считаются, а окно `except` считается всегда, чтобы не выбрать его
+    /// собственный стол. Новый стол сразу публикуется.
+    fn empty_workspace(&mut self, except: Option<Window>) -> Result<Option<usize>, ReplyOrIdError> {
+        let (config, clients) = (&self.config, &self.clients);
+        let monitor = &mut self.monitors[self.selected];
+        let count = monitor.workspaces.len();
+        let index = monitor.empty_workspace(config, |w| {
+            Some(w) == except || clients.get(&w).is_some_and(|c| !c.sticky)
+        });
+        if monitor.workspaces.len() != count {
+            self.update_desktops()?;
         }
+        Ok(index)
     }
 
-    fn arrange_windows(&mut self) -> Result<(), ConnectionError> {
-        let screen = &self.conn.setup().roots[self.screen_num];
-        let width = screen.width_in_pixels as u32;
-        let height = screen.height_in_pixels as u32;
-
-        if self.windows.len() >= 2 {
-            match self.layout {
-                LayoutMode::Horizontal => {
-                    self.conn.configure_window(
-                        self.windows[0],
-                        &ConfigureWindowAux::new()
-                            .x(0)
-                            .y(0)
-                            .width(width / 2)
-                            .height(height),
-                    )?;
-                    self.conn.configure_window(
-                        self.windows[1],
-                        &ConfigureWindowAux::new()
-                            .x((width / 2) as i32)
-                            .y(0)
-                            .width(width / 2)
-                            .height(height),
-                    )?;
+    fn view_empty_workspace(&mut self) -> Result<(), ReplyOrIdError> {
+        match self.empty_workspace(None)? {
+            Some(index) => self.view_workspace(index),
+            None => Ok(()),
+        }
+    }
+
+    /// Переносит сфокусированное окно на первый стол, где нет других окон.
+    fn move_to_empty_workspace(&mut self, follow: bool) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        match self.empty_workspace(Some(window))? {
+            Some(index) => self.move_to_workspace(index, follow),
+            None => Ok(()),
+        }
+    }
+
+    /// Делает текущим другой монитор: на нём появляются новые окна и
+    /// работают команды, фокус переходит к его сфокусированному окну.
+    fn focus_monitor(&mut self, target: &MonitorTarget) -> Result<(), ReplyOrIdError> {
+        let Some(index) = target.resolve(&self.monitors, self.selected) else {
+            return Ok(());
+        };
+        if index == self.selected {
+            return Ok(());
+        }
+        self.select_monitor(index)?;
+        if self.config.warp_pointer {
+            self.warp_pointer()?;
+        }
+        Ok(())
+    }
+
+    /// Делает монитор `index` текущим и отдаёт фокус его столу.
+    fn select_monitor(&mut self, index: usize) -> Result<(), ReplyOrIdError> {
+        if index == self.selected {
+            return Ok(());
+        }
+        if self.presel.is_some() {
+            self.cancel_preselect()?;
+        }
+        self.close_overview(None)?;
+        self.selected = index;
+        println!("Монитор {}", self.monitor().name);
+        // Подсветка вкладок следует за фокусом.
+        self.arrange_windows()?;
+        self.focus(self.workspace().focused)
+    }
+
+    /// Переносит указатель к окну, получившему фокус с клавиатуры.
+    fn warp_after_key(&self, focused: Option<Window>) -> Result<(), ReplyOrIdError> {
+        if self.config.warp_pointer && self.input_focus.is_some() && self.input_focus != focused {
+            self.warp_pointer()?;
+        }
+        Ok(())
+    }
+
+    /// Переносит указатель в центр окна с фокусом, а если его нет —
+    /// в центр текущего монитора.
+    fn warp_pointer(&self) -> Result<(), ReplyOrIdError> {
+        let rect = self
+            .input_focus
+            .and_then(|w| self.window_geometry(w))
+            .unwrap_or(self.monitor().area);
+        let (x, y) = rect.center();
+        let root = self.conn.setup().roots[self.screen_num].root;
+        self.conn
+            .warp_pointer(x11rb::NONE, root, 0, 0, 0, 0, x as i16, y as i16)?;
+        Ok(())
+    }
+
+    /// Переносит сфокусированное окно на основной стол другого монитора;
+    /// там оно встаёт в раскладку, а плавающая геометрия сдвигается вместе
+    /// с началом монитора. Фокус остаётся на текущем мониторе.
+    fn move_to_monitor(&mut self, target: &MonitorTarget) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        let Some(index) = target.resolve(&self.monitors, self.selected) else {
+            return Ok(());
+        };
+        if index == self.selected {
+            return Ok(());
+        }
+        if self.presel.is_some_and(|(w, _)| w == window) {
+            self.cancel_preselect()?;
+        }
+
+        let (from, to) = (self.monitor().area, self.monitors[index].area);
+        if let Some(client) = self.clients.get_mut(&window) {
+            client.float_geometry.x += to.x - from.x;
+            client.float_geometry.y += to.y - from.y;
+        }
+        self.transfer_to_monitor(window, index);
+
+        self.show_hide()?;
+        self.arrange_windows()?;
+        self.focus(self.workspace().focused)
+    }
+
+    /// Переводит окно на основной стол монитора `index` и делает его там
+    /// сфокусированным; закреплённое окно получает все столы монитора.
+    fn transfer_to_monitor(&mut self, window: Window, index: usize) {
+        let Some(from) = self.clients.get(&window).map(|c| c.monitor) else {
+            return;
+        };
+        for workspace in &mut self.monitors[from].workspaces {
+            workspace.remove(window);
+        }
+        let tags = self.monitors[index].all_tags();
+        let current = self.monitors[index].current;
+        let Some(client) = self.clients.get_mut(&window) else {
+            return;
+        };
+        client.monitor = index;
+        let tags = if client.sticky { tags } else { 1 << current };
+        self.set_tags(window, tags);
+        self.monitors[index].workspace_mut().focused = Some(window);
+        println!(
+            "Окно {} перенесено на монитор {}",
+            window, self.monitors[index].name
+        );
+    }
+
+    /// Если большая часть плавающего окна оказалась на другом мониторе,
+    /// окно переходит на его основной стол, а монитор становится текущим.
+    fn follow_floating_monitor(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        let Some(client) = self.clients.get(&window) else {
+            return Ok(());
+        };
+        if client.scratchpad.is_some() {
+            return Ok(());
+        }
+        let rect = client.float_geometry;
+        let best = self
+            .monitors
+            .iter()
+            .enumerate()
+            .map(|(i, m)| (i, m.area.overlap(rect)))
+            .max_by_key(|&(_, overlap)| overlap)
+            .filter(|&(_, overlap)| overlap > 0)
+            .map(|(i, _)| i);
+        let Some(index) = best.filter(|&i| i != client.monitor) else {
+            return Ok(());
+        };
+        if self.presel.is_some_and(|(w, _)| w == window) {
+            self.cancel_preselect()?;
+        }
+        self.transfer_to_monitor(window, index);
+        self.show_hide()?;
+        self.arrange_windows()?;
+        self.focus(Some(window))
+    }
+
+    /// Переводит фокус на ближайшее в направлении видимое окно монитора
+    /// по их настоящей геометрии; скрытые вкладки не учитываются.
+    fn focus_direction(&mut self, direction: Direction) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.workspace().focused else {
+            return Ok(());
+        };
+        let Some(from) = self.window_geometry(window) else {
+            return Ok(());
+        };
+        let area = self.monitor().area;
+        let candidates: Vec<(Window, Rect)> = self
+            .visible_windows()
+            .into_iter()
+            .filter(|&w| w != window)
+            .filter_map(|w| Some((w, self.window_geometry(w)?)))
+            .filter(|&(_, rect)| area.overlap(rect) > 0)
+            .collect();
+        match nearest(from, candidates, direction) {
+            Some(next) => self.focus(Some(next)),
+            None => Ok(()),
+        }
+    }
+
+    /// Листает видимые окна в порядке последнего фокуса: первое нажатие
+    /// возвращает к предыдущему окну, следующие идут дальше в историю.
+    /// Окна, ещё не бывшие в фокусе, идут в конце.
+    fn focus_mru(&mut self, forward: bool) -> Result<(), ReplyOrIdError> {
+        match self.step_mru(forward) {
+            Some(window) => self.focus(Some(window)),
+            None => Ok(()),
+        }
+    }
+
+    /// Шаг листания истории фокуса; возвращает окно, на которое он пришёл.
+    fn step_mru(&mut self, forward: bool) -> Option<Window> {
+        let (windows, position) = match self.mru_cycle.take() {
+            Some(cycle) => cycle,
+            None => {
+                let visible = self.visible_windows();
+                let mut windows: Vec<Window> = self
+                    .workspace()
+                    .mru
+                    .iter()
+                    .copied()
+                    .filter(|w| visible.contains(w))
+                    .collect();
+                for window in visible {
+                    if !windows.contains(&window) {
+                        windows.push(window);
+                    }
                 }
-                LayoutMode::Vertical => {
-                    self.conn.configure_window(
-                        self.windows[0],
-                        &ConfigureWindowAux::new()
-                            .x(0)
-                            .y(0)
-                            .width(width)
-                            .height(height / 2),
-                    )?;
-                    self.conn.configure_window(
-                        self.windows[1],
-                        &ConfigureWindowAux::new()
-                            .x(0)
-                            .y((height / 2) as i32)
-                            .width(width)
-                            .height(height / 2),
-                    )?;
+                (windows, 0)
+            }
+        };
+        let windows: Vec<Window> = windows
+            .into_iter()
+            .filter(|&w| self.is_visible(w))
+            .collect();
+        if windows.len() < 2 {
+            return None;
+        }
+        let len = windows.len();
+        let position = position.min(len - 1);
+        let next = if forward {
+            (position + 1) % len
+        } else {
+            (position + len - 1) % len
+        };
+        let window = windows[next];
+        self.mru_cycle = Some((windows, next));
+        Some(window)
+    }
+
+    /// Начинает Alt-Tab: делает первый шаг по истории и захватывает
+    /// клавиатуру, чтобы узнать об отпускании Alt. Если захватить её не
+    /// удалось, фокус переходит сразу, как при листании без захвата.
+    fn start_alt_tab(&mut self, forward: bool) -> Result<(), ReplyOrIdError> {
+        let Some(window) = self.step_mru(forward) else {
+            return Ok(());
+        };
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let grab = self
+            .conn
+            .grab_keyboard(
+                false,
+                root,
+                x11rb::CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?
+            .reply()?;
+        if grab.status != GrabStatus::SUCCESS {
+            return self.focus(Some(window));
+        }
+        self.select_alt_tab(window)?;
+        // Alt могли отпустить раньше, чем захват вступил в силу.
+        let keys = self.conn.query_keymap()?.reply()?.keys;
+        let pressed = |keycode: usize| keys[keycode / 8] & (1 << (keycode % 8)) != 0;
+        let alt = ALT_KEYSYMS.iter().flat_map(|&k| self.keymap.keycodes(k));
+        if !alt.into_iter().any(|k| pressed(k.into())) {
+            self.finish_alt_tab()?;
+        }
+        Ok(())
+    }
+
+    /// Клавиши во время Alt-Tab: Tab и Shift+Tab листают, отпускание Alt
+    /// закрепляет выбор.
+    fn handle_alt_tab_key(&mut self, event: &KeyPressEvent) -> Result<(), ReplyOrIdError> {
+        if event.response_type & 0x7f == KEY_RELEASE_EVENT {
+            if self
+                .keymap
+                .keysym(event.detail)
+                .is_some_and(|k| ALT_KEYSYMS.contains(&k))
+            {
+                self.finish_alt_tab()?;
+            }
+            return Ok(());
+        }
+        if self.keymap.keysym(event.detail) == Some(XK_TAB) {
+            let backward = u16::from(event.state) & u16::from(KeyButMask::SHIFT) != 0;
+            if let Some(window) = self.step_mru(!backward) {
+                self.select_alt_tab(window)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Переносит рамку фокуса на выбранное в Alt-Tab окно, не отдавая ему
+    /// фокус.
+    fn select_alt_tab(&mut self, window: Window) -> Result<(), ReplyOrIdError> {
+        let previous = self.alt_tab.replace(window).or(self.input_focus);
+        if previous != Some(window) {
+            if let Some(previous) = previous {
+                self.update_border(previous, false)?;
+            }
+            self.update_border(window, true)?;
+        }
+        Ok(())
+    }
+
+    /// Завершает Alt-Tab: отдаёт фокус выбранному окну, а если оно успело
+    /// пропасть, возвращает рамку окну с фокусом.
+    fn finish_alt_tab(&mut self) -> Result<(), ReplyOrIdError> {
+        let selected = self.alt_tab.take();
+        self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        match selected {
+            Some(window) if self.is_visible(window) => self.focus(Some(window))?,
+            Some(window) => {
+                self.update_border(window, false)?;
+                if let Some(focused) = self.input_focus {
+                    self.update_border(focused, true)?;
                 }
             }
+            None => {}
         }
+        self.end_mru_cycle();
         Ok(())
     }
 
-    fn toggle_layout(&mut self) -> Result<(), ConnectionError> {
-        self.layout = match self.layout {
-            LayoutMode::Horizontal => LayoutMode::Vertical,
-            LayoutMode::Vertical => LayoutMode::Horizontal,
+    /// Завершает листание истории: окно, на котором оно остановилось,
+    /// становится самым недавним.
+    fn end_mru_cycle(&mut self) {
+        if self.mru_cycle.take().is_some()
+            && let Some(window) = self.workspace().focused
+        {
+            self.touch(window);
+        }
+    }
+
+    /// Поднимает окно в начало истории фокуса стола и общей истории.
+    fn touch(&mut self, window: Window) {
+        self.workspace_mut().touch(window);
+        self.focus_history.retain(|&w| w != window);
+        self.focus_history.insert(0, window);
+    }
+
+    /// Переходит к последнему окну с фокусом, кроме текущего; повторный
+    /// вызов возвращает обратно.
+    fn focus_last(&mut self) -> Result<(), ReplyOrIdError> {
+        let last = self
+            .focus_history
+            .iter()
+            .copied()
+            .find(|&w| Some(w) != self.input_focus);
+        match last {
+            Some(window) => self.activate(window),
+            None => Ok(()),
+        }
+    }
+
+    /// Переводит фокус на соседнее окно списка; `forward` выбирает направление.
+    fn cycle_focus(&mut self, forward: bool) -> Result<(), ReplyOrIdError> {
+        let windows = self.visible_windows();
+        if windows.is_empty() {
+            return Ok(());
+        }
+        let len = windows.len();
+        let current = self
+            .workspace()
+            .focused
+            .and_then(|w| windows.iter().position(|&x| x == w))
+            .unwrap_or(0);
+        let next = if forward {
+            (current + 1) % len
+        } else {
+            (current + len - 1) % len
         };
-        self.arrange_windows()?;
-        Ok(())
+        self.focus(Some(windows[next]))
     }
 
-    fn setup_key_bindings(&self) -> Result<(), ConnectionError> {
+    /// Захватывает клавиши привязок по текущей раскладке, сняв прежние
+    /// захваты.
+    fn setup_key_bindings(&self) -> Result<(), ReplyOrIdError> {
         let screen = &self.conn.setup().roots[self.screen_num];
         let root = screen.root;
+        self.conn.ungrab_key(Grab::ANY, root, ModMask::ANY)?;
 
-        self.conn.grab_key(
-            true,
-            root,
-            ModMask::M4,
-            65,
-            GrabMode::ASYNC,
-            GrabMode::ASYNC,
-        )?;
+        for binding in &self.key_bindings {
+            for keycode in self.keymap.keycodes(binding.keysym) {
+                for ignored in ignored_modifiers() {
+                    self.conn.grab_key(
+                        true,
+                        root,
+                        binding.modifiers | ignored,
+                        keycode,
+                        GrabMode::ASYNC,
+                        GrabMode::ASYNC,
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn binding_for(&self, event: &KeyPressEvent) -> Option<Action> {
+        let ignored = (ModMask::LOCK | ModMask::M2).bits();
+        let state = u16::from(event.state) & !ignored;
+        let keysym = self.keymap.keysym(event.detail)?;
+        self.key_bindings
+            .iter()
+            .find(|b| b.keysym == keysym && b.modifiers.bits() == state)
+            .map(|b| b.action.clone())
+    }
+
+    /// Выполняет действие привязки или IPC-команды.
+    fn dispatch(&mut self, action: Action) -> Result<(), ReplyOrIdError> {
+        if !matches!(action, Action::FocusMru(_) | Action::AltTab(_)) {
+            self.end_mru_cycle();
+        }
+        match action {
+            Action::ToggleLayout => {
+                self.toggle_layout()?;
+                println!("Переключен режим на: {:?}", self.workspace().layout);
+            }
+            Action::TogglePseudoTiled => self.toggle_pseudo_tiled()?,
+            Action::ToggleSticky => self.toggle_sticky()?,
+            Action::ToggleFloating => self.toggle_floating()?,
+            Action::ToggleMaximize(horizontal, vertical) => {
+                self.toggle_maximize(horizontal, vertical)?;
+            }
+            Action::ToggleFullscreen => {
+                if let Some(window) = self.workspace().focused {
+                    let fullscreen = self.clients.get(&window).is_some_and(|c| c.fullscreen);
+                    self.set_fullscreen(window, !fullscreen)?;
+                }
+            }
+            Action::ToggleAbove => {
+                if let Some(window) = self.workspace().focused {
+                    let above = self.clients.get(&window).is_some_and(|c| c.above);
+                    self.set_above(window, !above)?;
+                }
+            }
+            Action::CenterFloating => self.center_focused()?,
+            Action::CloseWindow => {
+                if let Some(window) = self.workspace().focused {
+                    self.close_window(window)?;
+                }
+            }
+            Action::MoveFloating(direction) => self.nudge_floating(direction, false)?,
+            Action::ResizeFloating(direction) => self.nudge_floating(direction, true)?,
+            Action::ToggleScratchpad(name) => self.toggle_scratchpad(&name)?,
+            Action::GrowMaster => self.adjust_master(1.0)?,
+            Action::ShrinkMaster => self.adjust_master(-1.0)?,
+            Action::Mirror => self.toggle_transform(false)?,
+            Action::Rotate => self.toggle_transform(true)?,
+            Action::Balance => self.balance()?,
+            Action::IncMasterCount => self.adjust_master_count(1)?,
+            Action::DecMasterCount => self.adjust_master_count(-1)?,
+            Action::FocusNext => self.cycle_focus(true)?,
+            Action::FocusPrev => self.cycle_focus(false)?,
+            Action::FocusUrgent => self.focus_urgent()?,
+            Action::FocusLast => self.focus_last()?,
+            Action::FocusMru(forward) => self.focus_mru(forward)?,
+            Action::AltTab(forward) => self.start_alt_tab(forward)?,
+            Action::Spawn(command) => match spawn(&command) {
+                Ok(()) => println!("Запущено: {}", command),
+                Err(e) => println!("Не удалось запустить {}: {}", command, e),
+            },
+            Action::Command(command) => self.run_command(&command)?,
+            Action::FocusDirection(direction) => self.focus_direction(direction)?,
+            Action::SplitHorizontal => self.split_focused(true),
+            Action::SplitVertical => self.split_focused(false),
+            Action::Preselect(direction) => self.preselect(direction)?,
+            Action::CancelPreselect => self.cancel_preselect()?,
+            Action::ToggleTabbed => self.toggle_container_layout(ContainerLayout::Tabbed)?,
+            Action::ToggleStacked => self.toggle_container_layout(ContainerLayout::Stacked)?,
+            Action::NextTab => self.cycle_tab(true)?,
+            Action::PrevTab => self.cycle_tab(false)?,
+            Action::MoveToWorkspace(index, follow) => self.move_to_workspace(index, follow)?,
+            Action::ViewWorkspace(index) => self.view_workspace(index)?,
+            Action::ToggleView(index) => self.toggle_view(index)?,
+            Action::ViewAll => self.view_all()?,
+            Action::RenameWorkspace(name) => self.rename_workspace(name)?,
+            Action::ToggleTag(index) => self.toggle_tag(index)?,
+            Action::EmptyWorkspace => self.view_empty_workspace()?,
+            Action::MoveToEmptyWorkspace(follow) => self.move_to_empty_workspace(follow)?,
+            Action::PreviousWorkspace => self.view_workspace(self.monitor().previous)?,
+            Action::FocusMonitor(target) => self.focus_monitor(&target)?,
+            Action::MoveToMonitor(target) => self.move_to_monitor(&target)?,
+            Action::Overview => self.toggle_overview()?,
+            Action::SwitchProfile(name) => self.switch_profile(&name)?,
+            Action::Query(query) => self.answer(query)?,
+            Action::Quit => {
+                println!("Выход из оконного менеджера");
+                self.running = false;
+            }
+            Action::Reload => self.reload_config()?,
+        }
+        Ok(())
+    }
+
+    fn answer(&self, query: ipc::Query) -> Result<(), ReplyOrIdError> {
+        let text = match query {
+            ipc::Query::PrimaryMonitor => {
+                self.monitors[primary_monitor(&self.monitors)].name.clone()
+            }
+            ipc::Query::Title => self
+                .workspace()
+                .focused
+                .map(|w| self.window_title(w))
+                .unwrap_or_default(),
+            ipc::Query::Windows => self
+                .client_list
+                .iter()
+                .filter_map(|&window| {
+                    let client = self.clients.get(&window)?;
+                    let pid = client.pid.map_or("-".to_string(), |pid| pid.to_string());
+                    Some(format!(
+                        "{} {} {} {}",
+                        window,
+                        pid,
+                        client.class,
+                        self.window_title(window)
+                    ))
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            ipc::Query::Urgent => self
+                .urgent
+                .iter()
+                .filter_map(|&window| {
+                    let client = self.clients.get(&window)?;
+                    let place = match &client.scratchpad {
+                        Some(name) => format!("scratchpad:{}", name),
+                        None => {
+                            let index = client.tags.trailing_zeros() as usize;
+                            let monitor = &self.monitors[client.monitor];
+                            monitor.workspaces.get(index)?.label(index)
+                        }
+                    };
+                    Some(format!(
+                        "{} {} {}",
+                        window,
+                        place,
+                        self.window_title(window)
+                    ))
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        let root = self.conn.setup().roots[self.screen_num].root;
+        ipc::reply(&self.conn, root, self.atoms._REWM_REPLY, &text)
+    }
+
+    /// Применяет первый профиль, выходы которого совпадают с подключёнными.
+    fn apply_matching_profile(&mut self) -> Result<(), ReplyOrIdError> {
+        let mut connected: Vec<&str> = self.monitors.iter().map(|m| m.name.as_str()).collect();
+        connected.sort_unstable();
+        let matching = self.config.profiles.iter().position(|profile| {
+            profile
+                .outputs
+                .keys()
+                .map(String::as_str)
+                .eq(connected.iter().copied())
+        });
+        match matching {
+            Some(index) => self.apply_profile(index),
+            None => Ok(()),
+        }
+    }
+
+    /// Раздаёт столы профиля `index` его выходам; отсутствующие выходы
+    /// пропускаются.
+    fn apply_profile(&mut self, index: usize) -> Result<(), ReplyOrIdError> {
+        let profile = &self.config.profiles[index];
+        println!("Профиль мониторов {}", profile.name);
+        for (output, names) in &profile.outputs {
+            if let Some(monitor) = self.monitors.iter_mut().find(|m| &m.name == output) {
+                monitor.name_workspaces(names, &self.config);
+            }
+        }
+        self.update_desktops()?;
+        self.update_bars()
+    }
+
+    fn switch_profile(&mut self, name: &str) -> Result<(), ReplyOrIdError> {
+        match self.config.profiles.iter().position(|p| p.name == name) {
+            Some(index) => self.apply_profile(index),
+            None => {
+                println!("Нет профиля {}", name);
+                Ok(())
+            }
+        }
+    }
+
+    /// Запускает команды `autostart`. Зовётся один раз, когда менеджер
+    /// уже управляет корневым окном, так что окна программ попадут к нему.
+    fn autostart(&self) {
+        for command in &self.config.autostart {
+            match spawn(command) {
+                Ok(()) => println!("Запущено приложение: {}", command),
+                Err(e) => println!("Не удалось запустить {}: {}", command, e),
+            }
+        }
+    }
+
+    /// События корневого окна. Нажатия над ним приходят, только когда под
+    /// указателем нет других окон, так что прокрутка внутри приложений
+    /// не страдает.
+    fn root_event_mask(&self) -> EventMask {
+        let mut mask = EventMask::SUBSTRUCTURE_REDIRECT
+            | EventMask::SUBSTRUCTURE_NOTIFY
+            | EventMask::PROPERTY_CHANGE;
+        if self.config.root_scroll {
+            mask |= EventMask::BUTTON_PRESS;
+        }
+        mask
+    }
+
+    /// Перечитывает файл настроек: заново захватывает клавиши и кнопки,
+    /// перекрашивает рамки и панели и расставляет окна. Окна, столы и их
+    /// раскладки остаются как были; новое число столов и раскладка
+    /// действуют для столов, созданных после этого.
+    fn reload_config(&mut self) -> Result<(), ReplyOrIdError> {
+        // С ошибкой в файле остаются прежние настройки: файл могли
+        // сохранить посреди правки.
+        let config = match Config::read(self.config_path.as_deref()) {
+            Ok(config) => config,
+            Err(e) => {
+                println!("Настройки не перечитаны: {}", e);
+                return Ok(());
+            }
+        };
+        let root_scroll = std::mem::replace(&mut self.config, config).root_scroll;
+        self.key_bindings = key_bindings(&self.config);
+        self.setup_key_bindings()?;
+        if self.config.root_scroll != root_scroll {
+            let root = self.conn.setup().roots[self.screen_num].root;
+            self.conn.change_window_attributes(
+                root,
+                &ChangeWindowAttributesAux::new().event_mask(self.root_event_mask()),
+            )?;
+        }
 
-        self.conn.grab_key(
-            true,
-            root,
-            ModMask::CONTROL | ModMask::M4,
-            24,
-            GrabMode::ASYNC,
-            GrabMode::ASYNC,
+        let colors = self.config.colors;
+        self.bars.set_colors(colors);
+        self.tab_bars.set_colors(colors);
+        self.conn.change_window_attributes(
+            self.presel_hint,
+            &ChangeWindowAttributesAux::new().background_pixel(colors.presel),
         )?;
+        let windows: Vec<Window> = self.clients.keys().copied().collect();
+        for window in windows {
+            let focused = self.input_focus == Some(window);
+            self.update_border(window, focused)?;
+            self.grab_buttons(window, focused)?;
+        }
+
+        // Плотность и панели мониторов зависят от настроек.
+        self.update_monitors()?;
+        self.arrange_windows()
+    }
+
+    /// Заново определяет мониторы после подключения или отключения выхода.
+    /// Мониторы сопоставляются по имени выхода; окна исчезнувших мониторов
+    /// переезжают на основной монитор на столы с теми же номерами.
+    fn update_monitors(&mut self) -> Result<(), ReplyOrIdError> {
+        let root = self.conn.setup().roots[self.screen_num].root;
+        let outputs = randr::detect(&self.conn, root)?;
+        if outputs.is_empty() {
+            // Единственный монитор во весь экран остаётся, но его плотность
+            // и панель могли смениться в настройках.
+            let output = screen_output(&self.conn.setup().roots[self.screen_num]);
+            for monitor in &mut self.monitors {
+                monitor.update(&output, &self.config);
+            }
+            return self.update_struts();
+        }
+        if self.presel.is_some() {
+            self.cancel_preselect()?;
+        }
+        self.close_overview(None)?;
+
+        let mut old: Vec<Option<Monitor>> = std::mem::take(&mut self.monitors)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut mapping = vec![None; old.len()];
+        for output in outputs {
+            let existing = old
+                .iter()
+                .position(|m| m.as_ref().is_some_and(|m| m.name == output.name));
+            let monitor = match existing.and_then(|i| Some((i, old[i].take()?))) {
+                Some((i, mut monitor)) => {
+                    mapping[i] = Some(self.monitors.len());
+                    monitor.update(&output, &self.config);
+                    monitor
+                }
+                None => {
+                    println!("Подключён монитор {}: {:?}", output.name, output.area);
+                    Monitor::new(&output, &self.config)
+                }
+            };
+            self.monitors.push(monitor);
+        }
 
+        let primary = primary_monitor(&self.monitors);
+        let mapping: Vec<usize> = mapping.into_iter().map(|m| m.unwrap_or(primary)).collect();
+        for orphan in old.into_iter().flatten() {
+            println!("Отключён монитор {}", orphan.name);
+            let target = &mut self.monitors[primary];
+            while target.workspaces.len() < orphan.workspaces.len() {
+                target.workspaces.push(Workspace::new(&self.config));
+            }
+            let area = target.work_area();
+            for (i, workspace) in orphan.workspaces.iter().enumerate() {
+                for window in workspace.tree.windows() {
+                    if !target.workspaces[i].tree.contains(window) {
+                        target.workspaces[i].insert(window, area);
+                    }
+                }
+            }
+        }
+        for client in self.clients.values_mut() {
+            client.monitor = mapping[client.monitor];
+        }
+        self.selected = mapping[self.selected];
+        // Закреплённые окна должны оказаться и на добавленных столах.
+        let sticky: Vec<Window> = self
+            .clients
+            .iter()
+            .filter(|(_, c)| c.sticky)
+            .map(|(&w, _)| w)
+            .collect();
+        for window in sticky {
+            let tags = self.monitors[self.clients[&window].monitor].all_tags();
+            self.set_tags(window, tags);
+        }
+
+        self.update_struts()?;
+        self.apply_matching_profile()?;
+        self.update_desktops()?;
+        self.show_hide()?;
+        self.arrange_windows()?;
+        self.focus(self.workspace().focused)
+    }
+
+    /// Выполняет IPC-команды, пришедшие через свойство корневого окна,
+    /// обновляет панель при смене заголовка окна, следит за urgency
+    /// и ограничениями размера.
+    fn handle_property_notify(
+        &mut self,
+        event: &PropertyNotifyEvent,
+    ) -> Result<(), ReplyOrIdError> {
+        let root = self.conn.setup().roots[self.screen_num].root;
+        if (event.atom == u32::from(AtomEnum::WM_NAME) || event.atom == self.atoms._NET_WM_NAME)
+            && self.clients.contains_key(&event.window)
+        {
+            return self.update_bars();
+        }
+        if event.atom == u32::from(AtomEnum::WM_NORMAL_HINTS)
+            && self.clients.contains_key(&event.window)
+        {
+            let hints = WmSizeHints::get_normal_hints(&self.conn, event.window)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .flatten();
+            if let Some(client) = self.clients.get_mut(&event.window) {
+                client.size_hints = hints;
+            }
+            return self.arrange_windows();
+        }
+        if (event.atom == u32::from(AtomEnum::WM_HINTS) || event.atom == self.atoms.WM_PROTOCOLS)
+            && self.clients.contains_key(&event.window)
+        {
+            let model = self.input_model(event.window);
+            if let Some(client) = self.clients.get_mut(&event.window) {
+                (client.input, client.take_focus) = model;
+            }
+            if event.atom == self.atoms.WM_PROTOCOLS {
+                return Ok(());
+            }
+            return self.update_urgency(event.window);
+        }
+        if [self.atoms._NET_WM_STRUT, self.atoms._NET_WM_STRUT_PARTIAL].contains(&event.atom)
+            && self.docks.contains(&event.window)
+        {
+            self.update_struts()?;
+            return self.arrange_windows();
+        }
+        if event.window != root
+            || event.atom != self.atoms._REWM_COMMAND
+            || event.state != Property::NEW_VALUE
+        {
+            return Ok(());
+        }
+        for command in ipc::take_commands(&self.conn, root, self.atoms._REWM_COMMAND)? {
+            println!("Команда: {}", command);
+            self.run_command(&command)?;
+        }
         Ok(())
     }
 
-    fn run(&mut self) -> Result<(), ConnectionError> {
+    /// Разбирает и выполняет команду `rewm msg`; столы ищутся на текущем
+    /// мониторе.
+    fn run_command(&mut self, command: &str) -> Result<(), ReplyOrIdError> {
+        let (config, monitor) = (&self.config, &mut self.monitors[self.selected]);
+        let count = monitor.workspaces.len();
+        let action = ipc::parse(command, |name| monitor.resolve_workspace(name, config));
+        if self.monitor().workspaces.len() != count {
+            self.update_desktops()?;
+        }
+        match action {
+            Some(action) => self.dispatch(action),
+            None => {
+                println!("Неизвестная команда: {}", command);
+                Ok(())
+            }
+        }
+    }
+
+    /// Ждёт следующее событие. Пока отложено обновление мониторов, ждёт
+    /// не дольше его срока и возвращает `None`, когда срок вышел.
+    fn next_event(&mut self) -> Result<Option<Event>, ReplyOrIdError> {
+        let Some(deadline) = self.monitors_changed else {
+            return Ok(Some(self.conn.wait_for_event()?));
+        };
+        loop {
+            if let Some(event) = self.conn.poll_for_event()? {
+                return Ok(Some(event));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                self.monitors_changed = None;
+                return Ok(None);
+            }
+            thread::sleep((deadline - now).min(Duration::from_millis(5)));
+        }
+    }
+
+    /// Захватывает экран и обрабатывает события до выхода. С `replace`
+    /// экран отбирается у уже запущенного менеджера; без него занятый
+    /// экран — ошибка.
+    fn run(&mut self, replace: bool) -> Result<(), Box<dyn std::error::Error>> {
         let screen = &self.conn.setup().roots[self.screen_num];
         let root = screen.root;
 
-        self.conn.change_window_attributes(
-            root,
-            &ChangeWindowAttributesAux::new()
-                .event_mask(EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY),
-        )?;
+        let Some(selection) = icccm::acquire_manager_selection(
+            &self.conn,
+            screen,
+            self.screen_num,
+            &self.atoms,
+            replace,
+        )?
+        else {
+            return Err(ALREADY_RUNNING.into());
+        };
 
+        let mask = self.root_event_mask();
+        // Перенаправление подструктуры корня достаётся только одному
+        // клиенту; BadAccess значит, что менеджер уже есть, пусть и без
+        // выбора WM_Sn.
+        let redirect = self
+            .conn
+            .change_window_attributes(root, &ChangeWindowAttributesAux::new().event_mask(mask))?
+            .check();
+        if let Err(ReplyError::X11Error(error)) = &redirect
+            && error.error_kind == ErrorKind::Access
+        {
+            return Err(ALREADY_RUNNING.into());
+        }
+        redirect?;
+
+        ewmh::advertise(&self.conn, screen, &self.atoms)?;
         self.setup_key_bindings()?;
+        randr::select_changes(&self.conn, root)?;
+        self.update_struts()?;
+        self.apply_matching_profile()?;
+        self.update_desktops()?;
+        self.autostart();
 
-        println!("Оконный менеджер запущен. Режим: {:?}", self.layout);
+        println!(
+            "Оконный менеджер запущен. Режим: {:?}",
+            self.workspace().layout
+        );
 
-        loop {
+        while self.running {
             self.conn.flush()?;
-            let event = self.conn.wait_for_event()?;
+            let Some(event) = self.next_event()? else {
+                self.update_monitors()?;
+                continue;
+            };
+            match &event {
+                Event::KeyPress(event) => self.user_time = event.time,
+                Event::ButtonPress(event) => self.user_time = event.time,
+                Event::EnterNotify(event) => self.user_time = event.time,
+                _ => {}
+            }
 
             match event {
                 Event::MapRequest(event) => {
                     println!("Получен запрос на отображение окна: {}", event.window);
-                    self.conn.map_window(event.window)?;
-                    self.windows.push(event.window);
-                    if self.windows.len() > 2 {
-                        self.windows.remove(0);
+                    // Окна скрытых столов отобразятся при переходе на них.
+                    self.end_mru_cycle();
+                    let new = !self.clients.contains_key(&event.window);
+                    if new {
+                        self.manage(event.window)?;
+                        if self.clients.contains_key(&event.window) {
+                            self.client_list.push(event.window);
+                            self.stack.push(event.window);
+                            self.update_client_list()?;
+                            // Правило могло развернуть окно на весь экран.
+                            self.update_net_wm_state(event.window)?;
+                        }
+                        self.place_new_floating(event.window)?;
                     }
-                    self.arrange_windows()?;
+                    let unknown = !self.clients.contains_key(&event.window);
+                    let visible = unknown || self.is_visible(event.window);
+                    if unknown {
+                        self.conn.map_window(event.window)?;
+                    }
+                    if visible {
+                        self.map(event.window)?;
+                        self.arrange_windows()?;
+                    }
+                    if new && !unknown {
+                        self.focus_new_window(event.window)?;
+                    } else if visible {
+                        self.focus(Some(event.window))?;
+                    }
+                }
+                Event::KeyPress(event) | Event::KeyRelease(event) if self.alt_tab.is_some() => {
+                    let focused = self.input_focus;
+                    self.handle_alt_tab_key(&event)?;
+                    self.warp_after_key(focused)?;
                 }
+                Event::KeyPress(event) if self.overview.is_some() => {
+                    self.handle_overview_key(&event)?;
+                }
+                Event::ButtonPress(event) => self.handle_button_press(&event)?,
+                Event::MotionNotify(event) => self.handle_drag_motion(&event)?,
+                Event::ButtonRelease(_) => self.finish_drag()?,
+                Event::EnterNotify(event) => self.handle_enter_notify(&event)?,
+                Event::ClientMessage(event) => self.handle_client_message(&event)?,
                 Event::KeyPress(event) => {
-                    let keycode = event.detail;
-                    let state = event.state;
-
-                    if state == (ModMask::M4.bits() as u16).into() && keycode == 65 {
-                        self.toggle_layout()?;
-                        println!("Переключен режим на: {:?}", self.layout);
-                    }
-                    else if state == ((ModMask::CONTROL | ModMask::M4).bits() as u16).into() && keycode == 24 {
-                        println!("Выход из оконного менеджера");
-                        break;
+                    let focused = self.input_focus;
+                    if let Some(action) = self.binding_for(&event) {
+                        self.dispatch(action)?;
                     }
+                    self.warp_after_key(focused)?;
+                }
+                Event::PropertyNotify(event) => self.handle_property_notify(&event)?,
+                // Сменилась раскладка: привязки захватываются заново.
+                Event::MappingNotify(event) if event.request == Mapping::KEYBOARD => {
+                    self.keymap = Keymap::load(&self.conn)?;
+                    self.setup_key_bindings()?;
+                }
+                Event::RandrScreenChangeNotify(_) => {
+                    self.monitors_changed = Some(Instant::now() + RANDR_DEBOUNCE);
+                }
+                Event::FocusIn(event) => self.handle_focus_change(&event, true)?,
+                Event::FocusOut(event) => self.handle_focus_change(&event, false)?,
+                Event::UnmapNotify(event) => self.handle_unmap_notify(&event)?,
+                Event::DestroyNotify(event) => self.unmanage(event.window)?,
+                Event::SelectionClear(event) if event.selection == selection => {
+                    println!("Экран передан другому оконному менеджеру");
+                    self.running = false;
                 }
-                Event::DestroyNotify(event) => {
-                    if let Some(pos) = self.windows.iter().position(|&x| x == event.window) {
-                        self.windows.remove(pos);
+                Event::ConfigureRequest(event) => {
+                    self.handle_configure_request(&event)?;
+                }
+                Event::Expose(event) if event.count == 0 => {
+                    self.tab_bars.redraw(&self.conn, event.window)?;
+                    self.bars.redraw(&self.conn, event.window)?;
+                    if let Some(overview) = &self.overview {
+                        overview.redraw(&self.conn, event.window)?;
                     }
                 }
                 _ => {}
@@ -165,20 +4191,125 @@ impl<C: Connection> WindowManager<C> {
     }
 }
 
+/// Просит менеджер перечитать настройки командой `reload` тем же путём,
+/// что и `rewm msg`: так просьба из другого потока будит цикл событий.
+fn request_reload() {
+    if let Err(e) = ipc::send("reload") {
+        println!("Не удалось попросить перечитать настройки: {}", e);
+    }
+}
+
+/// По SIGHUP настройки перечитываются.
+fn watch_sighup() -> std::io::Result<()> {
+    let mut signals = Signals::new([SIGHUP])?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            request_reload();
+        }
+    });
+    Ok(())
+}
+
+/// `rewm --check-config`: проверяет файл настроек без подключения к X
+/// и завершается; с замечаниями — кодом 1.
+fn check_config(explicit: Option<&Path>) -> ! {
+    let Some(path) = config::find(explicit) else {
+        println!("Файл настроек не найден, действуют значения по умолчанию");
+        std::process::exit(0);
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let problems = config::check(&text);
+    for (line, message) in &problems {
+        eprintln!("{}:{}: {}", path.display(), line, message);
+    }
+    if problems.is_empty() {
+        println!("{}: ошибок нет", path.display());
+        std::process::exit(0);
+    }
+    std::process::exit(1);
+}
+
+/// Сообщение о занятом экране.
+const ALREADY_RUNNING: &str =
+    "экраном уже управляет другой оконный менеджер; заменить его: rewm --replace";
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let apps = vec!["firefox", "alacritty"];
-    for app in apps {
-        Command::new(app)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()?;
-        println!("Запущено приложение: {}", app);
-        thread::sleep(Duration::from_secs(1));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(("msg", command)) = args.split_first().map(|(a, rest)| (a.as_str(), rest)) {
+        return ipc::send(&command.join(" "));
+    }
+    let replace = args.iter().any(|arg| arg == "--replace");
+    let config_path = match args.iter().position(|arg| arg == "--config") {
+        Some(i) => Some(PathBuf::from(
+            args.get(i + 1).ok_or("после --config нужен путь к файлу")?,
+        )),
+        None => None,
+    };
+    if args.iter().any(|arg| arg == "--check-config") {
+        check_config(config_path.as_deref());
+    }
+    if args.iter().any(|arg| arg == "--generate-config") {
+        let force = args.iter().any(|arg| arg == "--force");
+        match config::write_default(config_path.as_deref(), force) {
+            Ok(path) => println!("Записан файл настроек {}", path.display()),
+            Err(e) => {
+                eprintln!("rewm: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
     }
 
+    let config = Config::load(config_path.as_deref());
+    let watched = config
+        .watch_config
+        .then(|| config::find(config_path.as_deref()))
+        .flatten();
     let (conn, screen_num) = x11rb::connect(None)?;
-    let mut wm = WindowManager::new(conn, screen_num);
-    wm.run()?;
+    let mut wm = WindowManager::new(conn, screen_num, config, config_path)?;
+    watch_sighup()?;
+    if let Some(path) = watched
+        && let Err(e) = config::watch(&path, request_reload)
+    {
+        println!("Не удалось следить за {}: {}", path.display(), e);
+    }
+    if let Err(e) = wm.run(replace) {
+        eprintln!("rewm: {}", e);
+        std::process::exit(1);
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actions(config: &Config, modifiers: ModMask) -> Vec<Action> {
+        key_bindings(config)
+            .into_iter()
+            .filter(|b| b.modifiers == modifiers && b.keysym == XK_TAB)
+            .map(|b| b.action)
+            .collect()
+    }
+
+    #[test]
+    fn alt_tab_replaces_mod_tab_when_modifier_is_alt() {
+        let mut config = Config::default();
+        assert_eq!(actions(&config, ModMask::M1), [Action::AltTab(true)]);
+        assert_eq!(actions(&config, ModMask::M4), [Action::FocusMru(true)]);
+
+        config.modifier = config::Modifier::Mod1;
+        assert_eq!(actions(&config, ModMask::M1), [Action::AltTab(true)]);
+        assert_eq!(
+            actions(&config, ModMask::M1 | ModMask::SHIFT),
+            [Action::AltTab(false)]
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use x11rb::protocol::xproto::Window;
+
+use crate::bar::BAR_HEIGHT;
+use crate::config::Config;
+use crate::layout::{Direction, Rect, nearest};
+use crate::randr::Output;
+use crate::workspace::{MAX_WORKSPACES, Workspace};
+
+/// Монитор, выбранный командой: соседний по списку, ближайший
+/// в направлении или по имени выхода.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MonitorTarget {
+    Next,
+    Prev,
+    Direction(Direction),
+    Name(String),
+}
+
+impl MonitorTarget {
+    /// Индекс монитора среди `monitors` относительно монитора `from`.
+    pub fn resolve(&self, monitors: &[Monitor], from: usize) -> Option<usize> {
+        let len = monitors.len();
+        match self {
+            MonitorTarget::Next => Some((from + 1) % len),
+            MonitorTarget::Prev => Some((from + len - 1) % len),
+            MonitorTarget::Name(name) => monitors.iter().position(|m| &m.name == name),
+            &MonitorTarget::Direction(direction) => nearest(
+                monitors[from].area,
+                monitors
+                    .iter()
+                    .enumerate()
+                    .filter(|&(i, _)| i != from)
+                    .map(|(i, m)| (i, m.area)),
+                direction,
+            ),
+        }
+    }
+}
+
+/// Полосы у краёв монитора, занятые панелями вроде polybar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Struts {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+/// Монитор со своим набором рабочих столов, как в dwm и xmonad:
+/// переключение столов на одном мониторе не трогает остальные.
+pub struct Monitor {
+    /// Имя выхода RandR.
+    pub name: String,
+    pub area: Rect,
+    /// Монитор основного выхода RandR.
+    pub primary: bool,
+    /// Масштаб элементов менеджера по плотности пикселей выхода.
+    pub scale: f32,
+    /// Высота встроенной панели у верхнего края; 0 — панели нет.
+    pub bar_height: u32,
+    /// Края, занятые внешними панелями.
+    pub struts: Struts,
+    pub workspaces: Vec<Workspace>,
+    /// Основной видимый стол: его раскладка расставляет все видимые окна.
+    pub current: usize,
+    /// Стол, который был виден до текущего.
+    pub previous: usize,
+    /// Маска видимых тегов; бит `i` — стол `i`.
+    pub view: u32,
+}
+
+/// Плотность, для которой нарисованы размеры по умолчанию.
+const BASE_DPI: f32 = 96.0;
+
+impl Monitor {
+    pub fn new(output: &Output, config: &Config) -> Self {
+        let workspaces = (0..config.workspaces)
+            .map(|i| Workspace {
+                name: config.workspace_names.get(i).cloned(),
+                ..Workspace::new(config)
+            })
+            .collect();
+        let mut monitor = Monitor {
+            name: output.name.clone(),
+            area: output.area,
+            primary: false,
+            scale: 1.0,
+            bar_height: 0,
+            struts: Struts::default(),
+            workspaces,
+            current: 0,
+            previous: 0,
+            view: 1,
+        };
+        monitor.update(output, config);
+        monitor
+    }
+
+    /// Переносит на монитор геометрию, статус основного и плотность
+    /// выхода. Масштаб округляется до четвертей и не бывает меньше 1;
+    /// `config.dpi` перекрывает плотность из RandR. При `bar_primary_only`
+    /// панель есть только на основном мониторе.
+    pub fn update(&mut self, output: &Output, config: &Config) {
+        self.area = output.area;
+        self.primary = output.primary;
+        self.scale = config
+            .dpi
+            .or(output.dpi)
+            .map_or(1.0, |dpi| (dpi / BASE_DPI * 4.0).round() / 4.0)
+            .max(1.0);
+        self.bar_height = if config.show_bar && (self.primary || !config.bar_primary_only) {
+            self.scaled(BAR_HEIGHT)
+        } else {
+            0
+        };
+    }
+
+    /// Размер в пикселях, заданный для плотности 96 DPI.
+    pub fn scaled(&self, size: u32) -> u32 {
+        (size as f32 * self.scale).round() as u32
+    }
+
+    /// Учитывает панель со стратом `_NET_WM_STRUT_PARTIAL`: отступы от
+    /// краёв экрана `screen` и диапазоны, вдоль которых они действуют.
+    /// Монитору достаётся часть полосы, заходящая на него.
+    pub fn reserve(&mut self, strut: &[u32; 12], screen: (u32, u32)) {
+        let value = |i: usize| i64::from(strut[i]);
+        let range = |i: usize| (value(i), value(i + 1));
+        let [left, right, top, bottom] = [0, 1, 2, 3].map(value);
+        let (x0, y0) = (i64::from(self.area.x), i64::from(self.area.y));
+        let (x1, y1) = (
+            x0 + i64::from(self.area.width),
+            y0 + i64::from(self.area.height),
+        );
+        let (width, height) = (i64::from(screen.0), i64::from(screen.1));
+        let overlaps = |(start, end): (i64, i64), from: i64, to: i64| start < to && end >= from;
+        let depth = |amount: i64, size: u32| amount.clamp(0, i64::from(size)) as u32;
+        let struts = &mut self.struts;
+        if left > 0 && overlaps(range(4), y0, y1) {
+            struts.left = struts.left.max(depth(left - x0, self.area.width));
+        }
+        if right > 0 && overlaps(range(6), y0, y1) {
+            struts.right = struts
+                .right
+                .max(depth(x1 - (width - right), self.area.width));
+        }
+        if top > 0 && overlaps(range(8), x0, x1) {
+            struts.top = struts.top.max(depth(top - y0, self.area.height));
+        }
+        if bottom > 0 && overlaps(range(10), x0, x1) {
+            struts.bottom = struts
+                .bottom
+                .max(depth(y1 - (height - bottom), self.area.height));
+        }
+    }
+
+    /// Монитор без полос внешних панелей.
+    fn usable_area(&self) -> Rect {
+        let Struts {
+            left,
+            right,
+            top,
+            bottom,
+        } = self.struts;
+        let width = self.area.width.saturating_sub(left + right).max(1);
+        let height = self.area.height.saturating_sub(top + bottom).max(1);
+        Rect {
+            x: self.area.x + left.min(self.area.width - 1) as i32,
+            y: self.area.y + top.min(self.area.height - 1) as i32,
+            width,
+            height,
+        }
+    }
+
+    /// Область для окон: монитор без внешних панелей и полосы встроенной.
+    pub fn work_area(&self) -> Rect {
+        let area = self.usable_area();
+        let bar = self.bar_height.min(area.height);
+        Rect {
+            y: area.y + bar as i32,
+            height: area.height - bar,
+            ..area
+        }
+    }
+
+    /// Полоса встроенной панели: под внешней панелью у верхнего края.
+    pub fn bar_rect(&self) -> Rect {
+        let area = self.usable_area();
+        Rect {
+            height: self.bar_height.min(area.height),
+            ..area
+        }
+    }
+
+    pub fn workspace(&self) -> &Workspace {
+        &self.workspaces[self.current]
+    }
+
+    pub fn workspace_mut(&mut self) -> &mut Workspace {
+        &mut self.workspaces[self.current]
+    }
+
+    /// Называет первые столы монитора по списку, создавая недостающие.
+    pub fn name_workspaces(&mut self, names: &[String], config: &Config) {
+        for (i, name) in names.iter().take(MAX_WORKSPACES).enumerate() {
+            if self.workspaces.len() <= i {
+                self.workspaces.push(Workspace::new(config));
+            }
+            self.workspaces[i].name = Some(name.clone());
+        }
+    }
+
+    /// Маска всех существующих столов.
+    pub fn all_tags(&self) -> u32 {
+        u32::MAX >> (u32::BITS as usize - self.workspaces.len())
+    }
+
+    /// Окна видимых тегов: сначала окна основного стола, затем окна
+    /// остальных видимых столов, без повторов.
+    pub fn visible_windows(&self) -> Vec<Window> {
+        let mut windows = self.workspace().tree.windows();
+        for (i, workspace) in self.workspaces.iter().enumerate() {
+            if i == self.current || self.view & (1 << i) == 0 {
+                continue;
+            }
+            for window in workspace.tree.windows() {
+                if !windows.contains(&window) {
+                    windows.push(window);
+                }
+            }
+        }
+        windows
+    }
+
+    /// Первый стол, где нет занятых окон (`occupied`); если такого нет,
+    /// создаётся новый безымянный стол в конце.
+    pub fn empty_workspace(
+        &mut self,
+        config: &Config,
+        occupied: impl Fn(Window) -> bool,
+    ) -> Option<usize> {
+        let found = self
+            .workspaces
+            .iter()
+            .position(|ws| !ws.tree.windows().into_iter().any(&occupied));
+        if found.is_some() {
+            return found;
+        }
+        if self.workspaces.len() >= MAX_WORKSPACES {
+            return None;
+        }
+        self.workspaces.push(Workspace::new(config));
+        Some(self.workspaces.len() - 1)
+    }
+
+    /// Находит стол по номеру или имени из команды. Недостающие столы
+    /// создаются: по номеру — безымянными, по имени — с этим именем.
+    pub fn resolve_workspace(&mut self, token: &str, config: &Config) -> Option<usize> {
+        let index = match token.parse::<usize>() {
+            Ok(number) => number.checked_sub(1)?,
+            Err(_) => match self
+                .workspaces
+                .iter()
+                .position(|ws| ws.name.as_deref() == Some(token))
+            {
+                Some(index) => return Some(index),
+                None => self.workspaces.len(),
+            },
+        };
+        if index >= MAX_WORKSPACES {
+            return None;
+        }
+        while self.workspaces.len() <= index {
+            self.workspaces.push(Workspace::new(config));
+        }
+        if token.parse::<usize>().is_err() {
+            self.workspaces[index].name = Some(token.to_string());
+        }
+        Some(index)
+    }
+
+    /// Удаляет пустые безымянные столы сверх настроенных, если они
+    /// в конце списка и не видны: индексы остальных столов (а значит и
+    /// биты тегов) при этом не сдвигаются. Возвращает, удалено ли что-то.
+    pub fn collect_workspaces(&mut self, config: &Config) -> bool {
+        let count = self.workspaces.len();
+        while let Some(last) = self.workspaces.last() {
+            let index = self.workspaces.len() - 1;
+            let disposable = index >= config.workspaces
+                && last.name.is_none()
+                && last.tree.windows().is_empty()
+                && self.view & (1 << index) == 0;
+            if !disposable {
+                break;
+            }
+            self.workspaces.pop();
+        }
+        if self.previous >= self.workspaces.len() {
+            self.previous = self.current;
+        }
+        self.workspaces.len() != count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::fixture::AREA;
+
+    fn monitor(area: Rect) -> Monitor {
+        let output = Output {
+            name: "eDP-1".to_string(),
+            area,
+            primary: true,
+            dpi: None,
+        };
+        Monitor::new(&output, &Config::default())
+    }
+
+    #[test]
+    fn resolve_workspace_creates_missing_numbers() {
+        let config = Config::default();
+        let mut monitor = monitor(AREA);
+        assert_eq!(monitor.workspaces.len(), config.workspaces);
+        assert_eq!(monitor.resolve_workspace("2", &config), Some(1));
+        assert_eq!(monitor.resolve_workspace("12", &config), Some(11));
+        assert_eq!(monitor.workspaces.len(), 12);
+        assert_eq!(monitor.resolve_workspace("0", &config), None);
+        assert_eq!(monitor.resolve_workspace("33", &config), None);
+        assert_eq!(monitor.workspaces.len(), 12);
+    }
+
+    #[test]
+    fn resolve_workspace_by_name() {
+        let config = Config::default();
+        let mut monitor = monitor(AREA);
+        monitor.workspaces[0].name = Some("web".to_string());
+        assert_eq!(monitor.resolve_workspace("web", &config), Some(0));
+        assert_eq!(monitor.resolve_workspace("mail", &config), Some(9));
+        assert_eq!(monitor.workspaces[9].name.as_deref(), Some("mail"));
+        assert_eq!(monitor.resolve_workspace("mail", &config), Some(9));
+        assert_eq!(monitor.workspaces.len(), 10);
+    }
+
+    #[test]
+    fn reserve_takes_the_part_of_a_strut_over_the_monitor() {
+        // Два монитора рядом на экране 200×60: панель сверху над левым
+        // и панель у правого края экрана.
+        let mut top = [0; 12];
+        top[2] = 20;
+        top[9] = 99;
+        let mut right = [0; 12];
+        right[1] = 10;
+        right[7] = 59;
+
+        let mut left_monitor = monitor(AREA);
+        let mut right_monitor = monitor(Rect { x: 100, ..AREA });
+        for monitor in [&mut left_monitor, &mut right_monitor] {
+            monitor.reserve(&top, (200, 60));
+            monitor.reserve(&right, (200, 60));
+        }
+        assert_eq!(
+            left_monitor.struts,
+            Struts {
+                top: 20,
+                ..Struts::default()
+            }
+        );
+        assert_eq!(
+            right_monitor.struts,
+            Struts {
+                right: 10,
+                ..Struts::default()
+            }
+        );
+    }
+}
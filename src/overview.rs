@@ -0,0 +1,173 @@
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::xproto::*;
+
+use crate::config::Colors;
+use crate::layout::{Rect, grid};
+use crate::tabbar::latin1;
+
+/// Зазор между ячейками обзора.
+const GAP: u32 = 8;
+
+/// Обзор столов: сетка ячеек-заглушек поверх монитора, по ячейке на
+/// стол. Пока обзор открыт, клавиатура захвачена им.
+pub struct Overview {
+    colors: Colors,
+    gc: Gcontext,
+    ascent: i16,
+    windows: Vec<Window>,
+    /// Подпись стола и число его окон.
+    cells: Vec<(String, usize)>,
+    columns: usize,
+    current: usize,
+    selected: usize,
+}
+
+impl Overview {
+    /// Открывает обзор в области `area`; выделен стол `current`.
+    pub fn open(
+        conn: &impl Connection,
+        screen: &Screen,
+        area: Rect,
+        cells: Vec<(String, usize)>,
+        current: usize,
+        colors: Colors,
+    ) -> Result<Self, ReplyOrIdError> {
+        let font = conn.generate_id()?;
+        conn.open_font(font, b"fixed")?;
+        let info = conn.query_font(font)?.reply()?;
+        let gc = conn.generate_id()?;
+        conn.create_gc(
+            gc,
+            screen.root,
+            &CreateGCAux::new()
+                .font(font)
+                .foreground(colors.text)
+                .graphics_exposures(0),
+        )?;
+        conn.close_font(font)?;
+
+        let mut windows = Vec::new();
+        for rect in grid(area, cells.len()) {
+            let window = conn.generate_id()?;
+            conn.create_window(
+                screen.root_depth,
+                window,
+                screen.root,
+                (rect.x + GAP as i32 / 2) as i16,
+                (rect.y + GAP as i32 / 2) as i16,
+                rect.width.saturating_sub(GAP).max(1) as u16,
+                rect.height.saturating_sub(GAP).max(1) as u16,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &CreateWindowAux::new()
+                    .override_redirect(1)
+                    .background_pixel(colors.background)
+                    .event_mask(EventMask::EXPOSURE | EventMask::BUTTON_PRESS),
+            )?;
+            conn.map_window(window)?;
+            windows.push(window);
+        }
+        conn.grab_keyboard(
+            false,
+            screen.root,
+            x11rb::CURRENT_TIME,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+        )?;
+
+        let columns = (1..=cells.len())
+            .find(|c| c * c >= cells.len())
+            .unwrap_or(1);
+        Ok(Overview {
+            colors,
+            gc,
+            ascent: info.font_ascent,
+            windows,
+            cells,
+            columns,
+            current,
+            selected: current,
+        })
+    }
+
+    /// Убирает ячейки и отпускает клавиатуру.
+    pub fn close(self, conn: &impl Connection) -> Result<(), ReplyOrIdError> {
+        for window in self.windows {
+            conn.destroy_window(window)?;
+        }
+        conn.free_gc(self.gc)?;
+        conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        Ok(())
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Стол, которому принадлежит ячейка `window`.
+    pub fn cell_at(&self, window: Window) -> Option<usize> {
+        self.windows.iter().position(|&w| w == window)
+    }
+
+    /// Сдвигает выделение на `dx` ячеек по строке и `dy` строк.
+    pub fn move_selection(
+        &mut self,
+        conn: &impl Connection,
+        dx: isize,
+        dy: isize,
+    ) -> Result<(), ReplyOrIdError> {
+        let target = self.selected as isize + dx + dy * self.columns as isize;
+        if target < 0 || target as usize >= self.cells.len() {
+            return Ok(());
+        }
+        let previous = std::mem::replace(&mut self.selected, target as usize);
+        self.draw(conn, previous)?;
+        self.draw(conn, self.selected)
+    }
+
+    /// Перерисовывает ячейку после Expose; чужие окна игнорируются.
+    pub fn redraw(&self, conn: &impl Connection, window: Window) -> Result<(), ReplyOrIdError> {
+        match self.cell_at(window) {
+            Some(i) => self.draw(conn, i),
+            None => Ok(()),
+        }
+    }
+
+    fn draw(&self, conn: &impl Connection, index: usize) -> Result<(), ReplyOrIdError> {
+        let window = self.windows[index];
+        let (label, count) = &self.cells[index];
+        let color = if index == self.selected {
+            self.colors.focused
+        } else if index == self.current {
+            self.colors.unfocused
+        } else {
+            self.colors.background
+        };
+        let geometry = conn.get_geometry(window)?.reply()?;
+
+        conn.change_gc(self.gc, &ChangeGCAux::new().foreground(color))?;
+        conn.poly_fill_rectangle(
+            window,
+            self.gc,
+            &[Rectangle {
+                x: 0,
+                y: 0,
+                width: geometry.width,
+                height: geometry.height,
+            }],
+        )?;
+
+        conn.change_gc(
+            self.gc,
+            &ChangeGCAux::new()
+                .foreground(self.colors.text)
+                .background(color),
+        )?;
+        let text = format!("{} ({})", label, count);
+        let text = latin1(&text);
+        conn.image_text8(window, self.gc, 8, 4 + self.ascent, &text)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+//! Обнаружение мониторов через RandR: каждый включённый CRTC с
+//! подключёнными выходами — отдельный монитор. Зеркальные CRTC с одной
+//! и той же областью считаются одним монитором.
+
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::randr::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::Window;
+
+use crate::layout::Rect;
+use crate::xinerama;
+
+/// Выход RandR: имя (например, `eDP-1`) и занимаемая область экрана.
+pub struct Output {
+    pub name: String,
+    pub area: Rect,
+    /// Выход назначен основным (`xrandr --primary`).
+    pub primary: bool,
+    /// Плотность пикселей по физической ширине; неизвестна у проекторов
+    /// и виртуальных выходов.
+    pub dpi: Option<f32>,
+}
+
+/// Включённые выходы слева направо и сверху вниз. Без расширения
+/// RandR или с версией старше 1.3 возвращается пустой список.
+pub fn outputs(conn: &impl Connection, root: Window) -> Result<Vec<Output>, ReplyOrIdError> {
+    if conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        return Ok(Vec::new());
+    }
+    let version = conn.randr_query_version(1, 3)?.reply()?;
+    if (version.major_version, version.minor_version) < (1, 3) {
+        return Ok(Vec::new());
+    }
+    let resources = conn.randr_get_screen_resources_current(root)?.reply()?;
+    let primary = conn.randr_get_output_primary(root)?.reply()?.output;
+
+    let mut outputs: Vec<Output> = Vec::new();
+    for &crtc in &resources.crtcs {
+        let info = conn
+            .randr_get_crtc_info(crtc, resources.config_timestamp)?
+            .reply()?;
+        let is_primary = info.outputs.contains(&primary);
+        let Some(&output) = info.outputs.first() else {
+            continue;
+        };
+        if info.mode == 0 || info.width == 0 || info.height == 0 {
+            continue;
+        }
+        let area = Rect {
+            x: info.x.into(),
+            y: info.y.into(),
+            width: info.width.into(),
+            height: info.height.into(),
+        };
+        if let Some(mirror) = outputs.iter_mut().find(|o| o.area == area) {
+            mirror.primary |= is_primary;
+            continue;
+        }
+        let output = conn
+            .randr_get_output_info(output, resources.config_timestamp)?
+            .reply()?;
+        outputs.push(Output {
+            name: String::from_utf8_lossy(&output.name).into_owned(),
+            area,
+            primary: is_primary,
+            dpi: (output.mm_width > 0).then(|| area.width as f32 * 25.4 / output.mm_width as f32),
+        });
+    }
+    outputs.sort_by_key(|o| (o.area.x, o.area.y));
+    Ok(outputs)
+}
+
+/// Выходы RandR, а если их не удалось получить — экраны Xinerama.
+pub fn detect(conn: &impl Connection, root: Window) -> Result<Vec<Output>, ReplyOrIdError> {
+    let outputs = outputs(conn, root)?;
+    if !outputs.is_empty() {
+        return Ok(outputs);
+    }
+    xinerama::outputs(conn)
+}
+
+/// Подписывает корневое окно на изменения набора и геометрии выходов.
+pub fn select_changes(conn: &impl Connection, root: Window) -> Result<(), ReplyOrIdError> {
+    if conn
+        .extension_information(randr::X11_EXTENSION_NAME)?
+        .is_some()
+    {
+        conn.randr_select_input(root, randr::NotifyMask::SCREEN_CHANGE)?;
+    }
+    Ok(())
+}
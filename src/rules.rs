@@ -0,0 +1,194 @@
+//! Правила для окон, применяемые при их появлении. Условия правила
+//! сравниваются со свойствами окна; у подходящего окна действия правила
+//! меняют его стол, монитор, плавание, полноэкранность, рамку и фокус.
+
+use serde::Deserialize;
+
+/// Тип окна из `_NET_WM_WINDOW_TYPE`; окна без известного типа — обычные.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WindowType {
+    Normal,
+    Dialog,
+    Utility,
+    Splash,
+}
+
+/// Правило из настроек. Заданные условия (`class` … `window_type`)
+/// должны совпасть все; правило без условий подходит любому окну.
+/// Незаданные действия окно не меняют.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Экземпляр или класс WM_CLASS.
+    pub class: Option<String>,
+    /// Только экземпляр WM_CLASS.
+    pub instance: Option<String>,
+    /// Часть заголовка окна.
+    pub title: Option<String>,
+    /// WM_WINDOW_ROLE, например `pop-up` у окон браузера.
+    pub role: Option<String>,
+    pub window_type: Option<WindowType>,
+
+    /// Стол: номер с единицы или имя; недостающий стол создаётся.
+    pub workspace: Option<String>,
+    /// Перейти на этот стол вслед за окном.
+    #[serde(default)]
+    pub switch: bool,
+    /// Монитор по имени выхода RandR; стол ищется на нём.
+    pub monitor: Option<String>,
+    pub floating: Option<bool>,
+    pub fullscreen: Option<bool>,
+    /// Толщина рамки окна при 96 DPI.
+    pub border: Option<u32>,
+    /// Не отдавать окну фокус при появлении.
+    pub no_focus: Option<bool>,
+}
+
+/// Свойства окна, с которыми сравниваются условия.
+pub struct Properties<'a> {
+    pub class: &'a str,
+    pub instance: &'a str,
+    pub title: &'a str,
+    pub role: &'a str,
+    pub window_type: WindowType,
+}
+
+impl Rule {
+    fn matches(&self, window: &Properties) -> bool {
+        self.class
+            .as_ref()
+            .is_none_or(|class| class == window.class || class == window.instance)
+            && self
+                .instance
+                .as_ref()
+                .is_none_or(|instance| instance == window.instance)
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|title| window.title.contains(title.as_str()))
+            && self.role.as_ref().is_none_or(|role| role == window.role)
+            && self.window_type.is_none_or(|t| t == window.window_type)
+    }
+}
+
+/// Действия всех подходящих правил. Если одно действие задают несколько
+/// правил, берётся первое из них.
+#[derive(Default)]
+pub struct Actions {
+    /// Стол и переход на него.
+    pub workspace: Option<(String, bool)>,
+    pub monitor: Option<String>,
+    pub floating: Option<bool>,
+    pub fullscreen: Option<bool>,
+    pub border: Option<u32>,
+    pub no_focus: bool,
+}
+
+pub fn evaluate(rules: &[Rule], window: &Properties) -> Actions {
+    let mut actions = Actions::default();
+    let mut no_focus = None;
+    for rule in rules.iter().filter(|rule| rule.matches(window)) {
+        actions.workspace = actions
+            .workspace
+            .or_else(|| Some((rule.workspace.clone()?, rule.switch)));
+        actions.monitor = actions.monitor.or_else(|| rule.monitor.clone());
+        actions.floating = actions.floating.or(rule.floating);
+        actions.fullscreen = actions.fullscreen.or(rule.fullscreen);
+        actions.border = actions.border.or(rule.border);
+        no_focus = no_focus.or(rule.no_focus);
+    }
+    actions.no_focus = no_focus.unwrap_or(false);
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(text: &str) -> Vec<Rule> {
+        #[derive(Deserialize)]
+        struct File {
+            rules: Vec<Rule>,
+        }
+        toml::from_str::<File>(text).unwrap().rules
+    }
+
+    const FIREFOX: Properties = Properties {
+        class: "firefox",
+        instance: "Navigator",
+        title: "Mozilla Firefox",
+        role: "browser",
+        window_type: WindowType::Normal,
+    };
+
+    #[test]
+    fn first_rule_wins_each_action() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            class = "firefox"
+            workspace = "2"
+
+            [[rules]]
+            class = "Navigator"
+            workspace = "3"
+            switch = true
+            floating = true
+            no_focus = true
+
+            [[rules]]
+            no_focus = false
+            border = 0
+            "#,
+        );
+        let actions = evaluate(&rules, &FIREFOX);
+        assert_eq!(actions.workspace, Some(("2".to_string(), false)));
+        assert_eq!(actions.floating, Some(true));
+        assert!(actions.no_focus);
+        assert_eq!(actions.border, Some(0));
+        assert_eq!(actions.fullscreen, None);
+        assert_eq!(actions.monitor, None);
+    }
+
+    #[test]
+    fn all_conditions_must_match() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            class = "firefox"
+            role = "pop-up"
+            floating = true
+
+            [[rules]]
+            title = "Firefox"
+            window_type = "normal"
+            fullscreen = true
+
+            [[rules]]
+            instance = "firefox"
+            border = 0
+            "#,
+        );
+        let actions = evaluate(&rules, &FIREFOX);
+        assert_eq!(actions.floating, None);
+        assert_eq!(actions.fullscreen, Some(true));
+        assert_eq!(actions.border, None);
+
+        let dialog = Properties {
+            role: "pop-up",
+            window_type: WindowType::Dialog,
+            ..FIREFOX
+        };
+        let actions = evaluate(&rules, &dialog);
+        assert_eq!(actions.floating, Some(true));
+        assert_eq!(actions.fullscreen, None);
+    }
+
+    #[test]
+    fn no_rules_change_nothing() {
+        let actions = evaluate(&[], &FIREFOX);
+        assert!(actions.workspace.is_none() && actions.floating.is_none());
+        assert!(!actions.no_focus);
+    }
+}
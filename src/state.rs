@@ -0,0 +1,83 @@
+//! Состояние, переживающее перезапуск: последняя плавающая геометрия
+//! окон каждого приложения. Хранится строками `класс x y ширина высота`
+//! в `$XDG_STATE_HOME/rewm/floating`, по умолчанию в `~/.local/state`.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::layout::Rect;
+
+pub struct FloatingMemory {
+    /// Файл состояния; `None`, если не удалось найти домашний каталог.
+    path: Option<PathBuf>,
+    geometries: HashMap<String, Rect>,
+}
+
+impl FloatingMemory {
+    /// Читает файл состояния; отсутствующий или испорченный файл даёт
+    /// пустую память.
+    pub fn load() -> Self {
+        let path = env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+            .map(|dir| dir.join("rewm").join("floating"));
+        let geometries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().filter_map(parse_line).collect())
+            .unwrap_or_default();
+        FloatingMemory { path, geometries }
+    }
+
+    pub fn get(&self, class: &str) -> Option<Rect> {
+        self.geometries.get(class).copied()
+    }
+
+    /// Запоминает геометрию приложения и, если она изменилась,
+    /// переписывает файл.
+    pub fn remember(&mut self, class: &str, rect: Rect) {
+        if class.is_empty() || self.geometries.get(class) == Some(&rect) {
+            return;
+        }
+        self.geometries.insert(class.to_string(), rect);
+        let Some(path) = &self.path else {
+            return;
+        };
+        let mut text = String::new();
+        for (class, rect) in &self.geometries {
+            text.push_str(&format!(
+                "{} {} {} {} {}\n",
+                class, rect.x, rect.y, rect.width, rect.height
+            ));
+        }
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, text));
+        if let Err(e) = written {
+            println!("Не удалось сохранить {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Строка `класс x y ширина высота`; класс может содержать пробелы.
+fn parse_line(line: &str) -> Option<(String, Rect)> {
+    let mut fields = line.rsplitn(5, ' ');
+    let height = fields.next()?.parse().ok()?;
+    let width = fields.next()?.parse().ok()?;
+    let y = fields.next()?.parse().ok()?;
+    let x = fields.next()?.parse().ok()?;
+    let class = fields.next()?.to_string();
+    Some((
+        class,
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        },
+    ))
+}
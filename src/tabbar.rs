@@ -0,0 +1,182 @@
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::xproto::*;
+
+use crate::config::Colors;
+use crate::tree::TabBar;
+
+pub const TAB_BAR_HEIGHT: u32 = 18;
+
+/// Текст для базового шрифта `fixed` в кодировке ISO 8859-1: символы вне
+/// Latin-1 заменяются на `?`, длина урезается до предела ImageText8.
+pub fn latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .take(255)
+        .collect()
+}
+
+/// Окна полос вкладок и заголовков стопки. Окна переиспользуются
+/// между перерисовками: лишние уничтожаются, недостающие создаются.
+pub struct TabBars {
+    colors: Colors,
+    gc: Gcontext,
+    ascent: i16,
+    descent: i16,
+    windows: Vec<Window>,
+    bars: Vec<(TabBar, Vec<String>)>,
+    focused: Option<Window>,
+}
+
+impl TabBars {
+    pub fn new(
+        conn: &impl Connection,
+        screen: &Screen,
+        colors: Colors,
+    ) -> Result<Self, ReplyOrIdError> {
+        let font = conn.generate_id()?;
+        conn.open_font(font, b"fixed")?;
+        let info = conn.query_font(font)?.reply()?;
+
+        let gc = conn.generate_id()?;
+        conn.create_gc(
+            gc,
+            screen.root,
+            &CreateGCAux::new()
+                .font(font)
+                .foreground(colors.text)
+                .graphics_exposures(0),
+        )?;
+        conn.close_font(font)?;
+
+        Ok(TabBars {
+            colors,
+            gc,
+            ascent: info.font_ascent,
+            descent: info.font_descent,
+            windows: Vec::new(),
+            bars: Vec::new(),
+            focused: None,
+        })
+    }
+
+    /// Меняет цвета полос; уже показанные перекрасятся при ближайшем
+    /// обновлении.
+    pub fn set_colors(&mut self, colors: Colors) {
+        self.colors = colors;
+    }
+
+    /// Показывает ровно `bars.len()` полос; к каждой приложены заголовки вкладок.
+    pub fn update(
+        &mut self,
+        conn: &impl Connection,
+        screen: &Screen,
+        bars: Vec<(TabBar, Vec<String>)>,
+        focused: Option<Window>,
+    ) -> Result<(), ReplyOrIdError> {
+        while self.windows.len() < bars.len() {
+            let window = conn.generate_id()?;
+            conn.create_window(
+                screen.root_depth,
+                window,
+                screen.root,
+                0,
+                0,
+                1,
+                1,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                screen.root_visual,
+                &CreateWindowAux::new()
+                    .override_redirect(1)
+                    .background_pixel(self.colors.background)
+                    .event_mask(EventMask::EXPOSURE),
+            )?;
+            self.windows.push(window);
+        }
+        for window in self.windows.drain(bars.len()..) {
+            conn.destroy_window(window)?;
+        }
+
+        for (&window, (bar, _)) in self.windows.iter().zip(&bars) {
+            conn.configure_window(
+                window,
+                &ConfigureWindowAux::new()
+                    .x(bar.rect.x)
+                    .y(bar.rect.y)
+                    .width(bar.rect.width.max(1))
+                    .height(bar.rect.height.max(1))
+                    .stack_mode(StackMode::ABOVE),
+            )?;
+            conn.map_window(window)?;
+        }
+
+        self.bars = bars;
+        self.focused = focused;
+        for i in 0..self.windows.len() {
+            self.draw(conn, i)?;
+        }
+        Ok(())
+    }
+
+    /// Перерисовывает полосу после Expose; чужие окна игнорируются.
+    pub fn redraw(&self, conn: &impl Connection, window: Window) -> Result<(), ReplyOrIdError> {
+        match self.windows.iter().position(|&w| w == window) {
+            Some(i) => self.draw(conn, i),
+            None => Ok(()),
+        }
+    }
+
+    fn draw(&self, conn: &impl Connection, index: usize) -> Result<(), ReplyOrIdError> {
+        let window = self.windows[index];
+        let (bar, titles) = &self.bars[index];
+        if bar.tabs.is_empty() {
+            return Ok(());
+        }
+
+        let count = bar.tabs.len() as u32;
+        let (width, height) = if bar.stacked {
+            (bar.rect.width as i16, (bar.rect.height / count) as i16)
+        } else {
+            ((bar.rect.width / count) as i16, bar.rect.height as i16)
+        };
+        let baseline = (height - self.ascent - self.descent) / 2 + self.ascent;
+
+        for (i, (&tab, title)) in bar.tabs.iter().zip(titles).enumerate() {
+            let color = if i != bar.active {
+                self.colors.background
+            } else if self.focused == Some(tab) {
+                self.colors.focused
+            } else {
+                self.colors.unfocused
+            };
+            let (x, y) = if bar.stacked {
+                (0, height * i as i16)
+            } else {
+                (width * i as i16, 0)
+            };
+
+            conn.change_gc(self.gc, &ChangeGCAux::new().foreground(color))?;
+            conn.poly_fill_rectangle(
+                window,
+                self.gc,
+                &[Rectangle {
+                    x,
+                    y,
+                    width: width as u16,
+                    height: height as u16,
+                }],
+            )?;
+
+            conn.change_gc(
+                self.gc,
+                &ChangeGCAux::new()
+                    .foreground(self.colors.text)
+                    .background(color),
+            )?;
+            let text = latin1(title);
+            conn.image_text8(window, self.gc, x + 4, y + baseline, &text)?;
+        }
+        Ok(())
+    }
+}
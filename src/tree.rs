@@ -0,0 +1,582 @@
+use x11rb::protocol::xproto::Window;
+
+use crate::layout::{Direction, Rect, split_weighted};
+
+/// Способ размещения детей контейнера.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerLayout {
+    /// Дети слева направо.
+    Horizontal,
+    /// Дети сверху вниз.
+    Vertical,
+    /// Дети занимают всю область по очереди, сверху полоса вкладок.
+    Tabbed,
+    /// Как вкладки, но заголовки детей идут друг под другом (stacking в i3).
+    Stacked,
+}
+
+impl ContainerLayout {
+    /// Показывает ли контейнер одного ребёнка с полосой заголовков.
+    pub fn has_titles(self) -> bool {
+        matches!(self, ContainerLayout::Tabbed | ContainerLayout::Stacked)
+    }
+}
+
+/// Узел дерева раскладки: либо окно, либо контейнер с дочерними узлами.
+#[derive(Clone)]
+pub enum Node {
+    Leaf(Window),
+    Split(Split),
+}
+
+#[derive(Clone)]
+pub struct Split {
+    pub layout: ContainerLayout,
+    pub children: Vec<Node>,
+    /// Относительные размеры детей, параллельно `children`.
+    pub weights: Vec<f32>,
+    /// Ребёнок, который последним получал фокус; для вкладок — видимый.
+    pub active: usize,
+}
+
+impl Split {
+    fn new(layout: ContainerLayout, children: Vec<Node>) -> Self {
+        Split {
+            layout,
+            weights: vec![1.0; children.len()],
+            children,
+            active: 0,
+        }
+    }
+
+    fn insert(&mut self, index: usize, node: Node) {
+        self.children.insert(index, node);
+        self.weights.insert(index, 1.0);
+    }
+
+    fn push(&mut self, node: Node) {
+        self.insert(self.children.len(), node);
+    }
+
+    fn remove(&mut self, index: usize) -> Node {
+        self.weights.remove(index);
+        self.children.remove(index)
+    }
+
+    /// Увеличивает долю ребёнка `index` на `delta` от размера контейнера,
+    /// пропорционально ужимая остальных.
+    fn resize(&mut self, index: usize, delta: f32) {
+        let total: f32 = self.weights.iter().sum();
+        let share = self.weights[index] / total;
+        let new_share = (share + delta).clamp(0.05, 0.95);
+        let scale = (1.0 - new_share) / (1.0 - share);
+        let count = self.weights.len() as f32;
+        for (i, weight) in self.weights.iter_mut().enumerate() {
+            let part = *weight / total;
+            *weight = count * if i == index { new_share } else { part * scale };
+        }
+    }
+
+    /// Выравнивает доли детей во всём поддереве.
+    fn balance(&mut self) {
+        self.weights.fill(1.0);
+        for child in &mut self.children {
+            if let Node::Split(split) = child {
+                split.balance();
+            }
+        }
+    }
+
+    fn active_child(&self) -> usize {
+        self.active.min(self.children.len().saturating_sub(1))
+    }
+}
+
+/// Полоса вкладок контейнера: по одному заголовку на каждого ребёнка.
+pub struct TabBar {
+    pub rect: Rect,
+    /// Заголовки друг под другом, а не в ряд.
+    pub stacked: bool,
+    /// Окно, чей заголовок показывается на вкладке ребёнка.
+    pub tabs: Vec<Window>,
+    pub active: usize,
+}
+
+/// Результат обхода дерева: видимые окна и полосы вкладок.
+/// Окна неактивных вкладок в `windows` не попадают.
+#[derive(Default)]
+pub struct Arrangement {
+    pub windows: Vec<(Window, Rect)>,
+    pub tab_bars: Vec<TabBar>,
+}
+
+impl Node {
+    fn contains(&self, window: Window) -> bool {
+        match self {
+            Node::Leaf(w) => *w == window,
+            Node::Split(split) => split.children.iter().any(|c| c.contains(window)),
+        }
+    }
+
+    fn collect(&self, out: &mut Vec<Window>) {
+        match self {
+            Node::Leaf(w) => out.push(*w),
+            Node::Split(split) => split.children.iter().for_each(|c| c.collect(out)),
+        }
+    }
+
+    /// Окно, которое представляет узел: последнее активное в поддереве.
+    fn active_leaf(&self) -> Option<Window> {
+        match self {
+            Node::Leaf(w) => Some(*w),
+            Node::Split(split) => split.children.get(split.active_child())?.active_leaf(),
+        }
+    }
+
+    fn arrange(&self, area: Rect, tab_height: u32, out: &mut Arrangement) {
+        let split = match self {
+            Node::Leaf(w) => return out.windows.push((*w, area)),
+            Node::Split(split) => split,
+        };
+        match split.layout {
+            ContainerLayout::Horizontal | ContainerLayout::Vertical => {
+                let horizontal = split.layout == ContainerLayout::Horizontal;
+                let parts = split_weighted(area, &split.weights, horizontal);
+                for (child, part) in split.children.iter().zip(parts) {
+                    child.arrange(part, tab_height, out);
+                }
+            }
+            ContainerLayout::Tabbed | ContainerLayout::Stacked => {
+                if split.children.is_empty() {
+                    return;
+                }
+                let stacked = split.layout == ContainerLayout::Stacked;
+                let rows = if stacked {
+                    split.children.len() as u32
+                } else {
+                    1
+                };
+                let bar_height = (tab_height * rows).min(area.height);
+                out.tab_bars.push(TabBar {
+                    rect: Rect {
+                        height: bar_height,
+                        ..area
+                    },
+                    stacked,
+                    tabs: split
+                        .children
+                        .iter()
+                        .filter_map(|c| c.active_leaf())
+                        .collect(),
+                    active: split.active_child(),
+                });
+                let body = Rect {
+                    y: area.y + bar_height as i32,
+                    height: area.height - bar_height,
+                    ..area
+                };
+                split.children[split.active_child()].arrange(body, tab_height, out);
+            }
+        }
+    }
+
+    fn leaf_mut(&mut self, window: Window) -> Option<&mut Node> {
+        match self {
+            Node::Leaf(w) if *w == window => Some(self),
+            Node::Leaf(_) => None,
+            Node::Split(split) => split.children.iter_mut().find_map(|c| c.leaf_mut(window)),
+        }
+    }
+
+    /// Контейнер, непосредственно содержащий лист `window`, и индекс листа в нём.
+    fn parent_mut(&mut self, window: Window) -> Option<(&mut Split, usize)> {
+        let Node::Split(split) = self else {
+            return None;
+        };
+        if let Some(i) = split
+            .children
+            .iter()
+            .position(|c| matches!(c, Node::Leaf(w) if *w == window))
+        {
+            return Some((split, i));
+        }
+        split.children.iter_mut().find_map(|c| c.parent_mut(window))
+    }
+
+    /// Отмечает путь до окна активным на каждом уровне.
+    fn set_active(&mut self, window: Window) {
+        if let Node::Split(split) = self
+            && let Some(pos) = split.children.iter().position(|c| c.contains(window))
+        {
+            split.active = pos;
+            split.children[pos].set_active(window);
+        }
+    }
+
+    /// Переключает ближайший к окну контейнер с заголовками на соседнюю
+    /// вкладку и возвращает окно, которое стоит сфокусировать.
+    fn cycle_tab(&mut self, window: Window, forward: bool) -> Option<Window> {
+        let Node::Split(split) = self else {
+            return None;
+        };
+        let pos = split.children.iter().position(|c| c.contains(window))?;
+        if let Some(next) = split.children[pos].cycle_tab(window, forward) {
+            return Some(next);
+        }
+        if !split.layout.has_titles() {
+            return None;
+        }
+        let len = split.children.len();
+        split.active = if forward {
+            (pos + 1) % len
+        } else {
+            (pos + len - 1) % len
+        };
+        split.children[split.active].active_leaf()
+    }
+
+    /// Меняет размер ближайшего к окну контейнера-разделителя.
+    fn resize(&mut self, window: Window, delta: f32) -> bool {
+        let Node::Split(split) = self else {
+            return false;
+        };
+        let Some(pos) = split.children.iter().position(|c| c.contains(window)) else {
+            return false;
+        };
+        if split.children[pos].resize(window, delta) {
+            return true;
+        }
+        if split.layout.has_titles() || split.children.len() < 2 {
+            return false;
+        }
+        split.resize(pos, delta);
+        true
+    }
+
+    /// Удаляет окно из поддерева. Контейнеры-разделители, в которых
+    /// остался один ребёнок, сворачиваются в этого ребёнка, пустые —
+    /// удаляются. Контейнеры с заголовками сохраняются и с одним окном.
+    fn remove(&mut self, window: Window) -> bool {
+        let Node::Split(split) = self else {
+            return false;
+        };
+        let Some(pos) = split.children.iter().position(|c| c.contains(window)) else {
+            return false;
+        };
+
+        let child = &mut split.children[pos];
+        if matches!(child, Node::Leaf(_)) {
+            split.remove(pos);
+        } else {
+            child.remove(window);
+            if let Node::Split(inner) = child {
+                match inner.children.len() {
+                    0 => {
+                        split.remove(pos);
+                    }
+                    1 if !inner.layout.has_titles() => {
+                        let only = inner.children.remove(0);
+                        split.children[pos] = only;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Дерево окон рабочего стола. Порядок листьев задаёт порядок окон
+/// для списочных раскладок, структура — геометрию для BSP.
+#[derive(Clone, Default)]
+pub struct Tree {
+    root: Option<Node>,
+}
+
+impl Tree {
+    pub fn contains(&self, window: Window) -> bool {
+        self.root.as_ref().is_some_and(|r| r.contains(window))
+    }
+
+    /// Окна в порядке обхода дерева слева направо.
+    pub fn windows(&self) -> Vec<Window> {
+        let mut out = Vec::new();
+        if let Some(root) = &self.root {
+            root.collect(&mut out);
+        }
+        out
+    }
+
+    pub fn arrange(&self, area: Rect, tab_height: u32) -> Arrangement {
+        let mut out = Arrangement::default();
+        if let Some(root) = &self.root {
+            root.arrange(area, tab_height, &mut out);
+        }
+        out
+    }
+
+    /// Добавляет окно в конец корневого контейнера.
+    pub fn push(&mut self, window: Window) {
+        match &mut self.root {
+            None => self.root = Some(Node::Leaf(window)),
+            Some(Node::Split(split)) => split.push(Node::Leaf(window)),
+            Some(leaf @ Node::Leaf(_)) => {
+                wrap(leaf, ContainerLayout::Horizontal, Node::Leaf(window))
+            }
+        }
+    }
+
+    /// Делит область окна `target` пополам вдоль длинной стороны и
+    /// помещает новое окно во вторую половину (как в bspwm).
+    /// Без подходящей цели окно просто добавляется в конец.
+    pub fn split_insert(&mut self, target: Option<Window>, window: Window, area: Rect) {
+        let Some(target) = target else {
+            return self.push(window);
+        };
+        let Some(rect) = self
+            .arrange(area, 0)
+            .windows
+            .into_iter()
+            .find(|(w, _)| *w == target)
+            .map(|(_, r)| r)
+        else {
+            return self.push(window);
+        };
+
+        let layout = if rect.width >= rect.height {
+            ContainerLayout::Horizontal
+        } else {
+            ContainerLayout::Vertical
+        };
+        if let Some(leaf) = self.root.as_mut().and_then(|r| r.leaf_mut(target)) {
+            wrap(leaf, layout, Node::Leaf(window));
+        }
+    }
+
+    /// Ставит окно сразу после `target` в его контейнере (как в i3).
+    pub fn insert_after(&mut self, target: Option<Window>, window: Window) {
+        let Some(target) = target else {
+            return self.push(window);
+        };
+        let Some(root) = &mut self.root else {
+            return self.push(window);
+        };
+        if matches!(root, Node::Leaf(w) if *w == target) {
+            return wrap(root, ContainerLayout::Horizontal, Node::Leaf(window));
+        }
+        match root.parent_mut(target) {
+            Some((split, i)) => split.insert(i + 1, Node::Leaf(window)),
+            None => self.push(window),
+        }
+    }
+
+    /// Делит окно `target` и ставит новое окно с указанной стороны от него.
+    pub fn insert_beside(&mut self, target: Window, window: Window, direction: Direction) {
+        let layout = if direction.is_horizontal() {
+            ContainerLayout::Horizontal
+        } else {
+            ContainerLayout::Vertical
+        };
+        let Some(leaf) = self.root.as_mut().and_then(|r| r.leaf_mut(target)) else {
+            return self.push(window);
+        };
+        wrap(leaf, layout, Node::Leaf(window));
+        if let (true, Node::Split(split)) = (direction.is_leading(), leaf) {
+            split.children.swap(0, 1);
+            split.active = 0;
+        }
+    }
+
+    /// Задаёт раскладку, в которой к окну `target` будут добавляться
+    /// следующие окна. Если окно — единственный ребёнок контейнера,
+    /// меняется раскладка самого контейнера, иначе окно оборачивается
+    /// в новый вложенный контейнер.
+    pub fn split(&mut self, target: Window, layout: ContainerLayout) {
+        let Some(root) = &mut self.root else {
+            return;
+        };
+        if matches!(root, Node::Leaf(w) if *w == target) {
+            *root = Node::Split(Split::new(layout, vec![Node::Leaf(target)]));
+            return;
+        }
+        if let Some((split, i)) = root.parent_mut(target) {
+            if split.children.len() == 1 {
+                split.layout = layout;
+            } else {
+                split.children[i] = Node::Split(Split::new(layout, vec![Node::Leaf(target)]));
+            }
+        }
+    }
+
+    /// Переключает контейнер окна между `layout` и горизонтальным делением.
+    pub fn toggle_layout(&mut self, target: Window, layout: ContainerLayout) {
+        let Some(root) = &mut self.root else {
+            return;
+        };
+        if matches!(root, Node::Leaf(w) if *w == target) {
+            return self.split(target, layout);
+        }
+        if let Some((split, i)) = root.parent_mut(target) {
+            split.active = i;
+            split.layout = if split.layout == layout {
+                ContainerLayout::Horizontal
+            } else {
+                layout
+            };
+        }
+    }
+
+    /// Увеличивает долю окна в его контейнере на `delta` (может быть < 0).
+    pub fn resize(&mut self, window: Window, delta: f32) {
+        if let Some(root) = &mut self.root {
+            root.resize(window, delta);
+        }
+    }
+
+    /// Выравнивает доли в контейнере окна и во всех вложенных.
+    pub fn balance(&mut self, window: Window) {
+        if let Some((split, _)) = self.root.as_mut().and_then(|r| r.parent_mut(window)) {
+            split.balance();
+        }
+    }
+
+    pub fn set_active(&mut self, window: Window) {
+        if let Some(root) = &mut self.root {
+            root.set_active(window);
+        }
+    }
+
+    pub fn cycle_tab(&mut self, window: Window, forward: bool) -> Option<Window> {
+        self.root.as_mut()?.cycle_tab(window, forward)
+    }
+
+    pub fn remove(&mut self, window: Window) -> bool {
+        let Some(root) = &mut self.root else {
+            return false;
+        };
+        if let Node::Leaf(w) = root {
+            if *w != window {
+                return false;
+            }
+            self.root = None;
+            return true;
+        }
+
+        let removed = root.remove(window);
+        if let Node::Split(split) = root {
+            match split.children.len() {
+                0 => self.root = None,
+                1 if !split.layout.has_titles() => self.root = Some(split.children.remove(0)),
+                _ => {}
+            }
+        }
+        removed
+    }
+}
+
+/// Заменяет узел контейнером из него самого и `sibling`.
+fn wrap(node: &mut Node, layout: ContainerLayout, sibling: Node) {
+    let placeholder = Node::Split(Split::new(layout, Vec::new()));
+    let old = std::mem::replace(node, placeholder);
+    if let Node::Split(split) = node {
+        split.push(old);
+        split.push(sibling);
+        split.active = 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::fixture::{AREA, rect};
+
+    fn rects(tree: &Tree) -> Vec<(Window, Rect)> {
+        tree.arrange(AREA, 10).windows
+    }
+
+    #[test]
+    fn split_insert_halves_the_long_side() {
+        let mut tree = Tree::default();
+        tree.split_insert(None, 1, AREA);
+        tree.split_insert(Some(1), 2, AREA);
+        tree.split_insert(Some(2), 3, AREA);
+        assert_eq!(
+            rects(&tree),
+            [
+                (1, rect(0, 0, 50, 60)),
+                (2, rect(50, 0, 50, 30)),
+                (3, rect(50, 30, 50, 30)),
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_collapses_single_child_splits() {
+        let mut tree = Tree::default();
+        tree.push(1);
+        tree.push(2);
+        tree.split_insert(Some(2), 3, AREA);
+        assert_eq!(tree.windows(), [1, 2, 3]);
+
+        assert!(tree.remove(3));
+        let Some(Node::Split(root)) = &tree.root else {
+            panic!("корень должен остаться контейнером");
+        };
+        assert!(root.children.iter().all(|c| matches!(c, Node::Leaf(_))));
+        assert_eq!(
+            rects(&tree),
+            [(1, rect(0, 0, 50, 60)), (2, rect(50, 0, 50, 60))]
+        );
+
+        assert!(tree.remove(1));
+        assert!(matches!(tree.root, Some(Node::Leaf(2))));
+        assert!(tree.remove(2));
+        assert!(tree.root.is_none());
+        assert!(!tree.remove(2));
+    }
+
+    #[test]
+    fn remove_keeps_tabbed_container_with_one_window() {
+        let mut tree = Tree::default();
+        tree.push(1);
+        tree.push(2);
+        tree.split(2, ContainerLayout::Tabbed);
+        tree.insert_after(Some(2), 3);
+        assert!(tree.remove(3));
+
+        let arrangement = tree.arrange(AREA, 10);
+        assert_eq!(arrangement.tab_bars.len(), 1);
+        assert_eq!(arrangement.tab_bars[0].tabs, [2]);
+        assert_eq!(
+            arrangement.windows,
+            [(1, rect(0, 0, 50, 60)), (2, rect(50, 10, 50, 50))]
+        );
+    }
+
+    #[test]
+    fn resize_and_remove_keep_weights_in_step() {
+        let mut tree = Tree::default();
+        tree.push(1);
+        tree.push(2);
+        tree.resize(1, 0.25);
+        assert_eq!(
+            rects(&tree),
+            [(1, rect(0, 0, 75, 60)), (2, rect(75, 0, 25, 60))]
+        );
+
+        tree.push(3);
+        assert!(tree.remove(2));
+        let Some(Node::Split(root)) = &tree.root else {
+            panic!("корень должен остаться контейнером");
+        };
+        assert_eq!(root.weights.len(), root.children.len());
+        let width: u32 = rects(&tree).iter().map(|(_, r)| r.width).sum();
+        assert_eq!(width, AREA.width);
+
+        tree.balance(1);
+        assert_eq!(
+            rects(&tree),
+            [(1, rect(0, 0, 50, 60)), (3, rect(50, 0, 50, 60))]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use x11rb::protocol::xproto::Window;
+
+use crate::config::Config;
+use crate::layout::{LayoutMode, LayoutParams, Rect, Transform};
+use crate::tree::Tree;
+
+/// Предел числа столов: теги окна хранятся в `u32`.
+pub const MAX_WORKSPACES: usize = u32::BITS as usize;
+
+/// Рабочий стол, он же тег: окна с этим тегом, фокус и раскладка.
+/// Окно с несколькими тегами входит в деревья нескольких столов.
+/// Раскладка и её параметры хранятся у каждого стола отдельно,
+/// как в патче pertag для dwm.
+pub struct Workspace {
+    /// Имя стола; безымянные столы сверх настроенных удаляются, опустев.
+    pub name: Option<String>,
+    /// Класс главного окна стола, если включено автоименование.
+    pub app: Option<String>,
+    pub tree: Tree,
+    /// Окно, получающее фокус при возврате на стол.
+    pub focused: Option<Window>,
+    /// Окна стола в порядке последнего фокуса, начиная с недавнего.
+    pub mru: Vec<Window>,
+    pub layout: LayoutMode,
+    pub transform: Transform,
+    pub params: LayoutParams,
+}
+
+impl Workspace {
+    pub fn new(config: &Config) -> Self {
+        Workspace {
+            name: None,
+            app: None,
+            tree: Tree::default(),
+            focused: None,
+            mru: Vec::new(),
+            layout: config.layout,
+            transform: Transform::default(),
+            params: LayoutParams {
+                master_factor: config.master_factor,
+                nmaster: config.nmaster,
+                deck_shown: 0,
+            },
+        }
+    }
+
+    /// Имя стола, а для безымянного — его номер с единицы. При
+    /// автоименовании к нему через двоеточие добавляется класс приложения.
+    pub fn label(&self, index: usize) -> String {
+        let base = self.name.clone().unwrap_or_else(|| (index + 1).to_string());
+        match &self.app {
+            Some(app) => format!("{}: {}", base, app),
+            None => base,
+        }
+    }
+
+    /// Добавляет окно по правилам раскладки стола: в BSP оно делит
+    /// сфокусированное, в дереве встаёт рядом с ним, иначе — в конец списка.
+    pub fn insert(&mut self, window: Window, area: Rect) {
+        match self.layout {
+            LayoutMode::Bsp => self.tree.split_insert(self.focused, window, area),
+            LayoutMode::Tree => self.tree.insert_after(self.focused, window),
+            _ => self.tree.push(window),
+        }
+    }
+
+    /// Поднимает окно в начало истории фокуса.
+    pub fn touch(&mut self, window: Window) {
+        self.mru.retain(|&w| w != window);
+        self.mru.insert(0, window);
+    }
+
+    /// Убирает окно со стола; фокус переходит к предыдущему по истории
+    /// окну стола, а без истории — к соседу по списку.
+    pub fn remove(&mut self, window: Window) {
+        self.mru.retain(|&w| w != window);
+        let pos = self.tree.windows().iter().position(|&w| w == window);
+        self.tree.remove(window);
+        if self.focused == Some(window) {
+            let remaining = self.tree.windows();
+            let pos = pos.unwrap_or(0).min(remaining.len().saturating_sub(1));
+            self.focused = self
+                .mru
+                .iter()
+                .copied()
+                .find(|w| remaining.contains(w))
+                .or_else(|| remaining.get(pos).copied());
+        }
+    }
+}
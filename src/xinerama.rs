@@ -0,0 +1,41 @@
+//! Запасной способ узнать мониторы, когда RandR недоступен или слишком
+//! стар: экраны Xinerama. Имён и плотности у них нет.
+
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyOrIdError;
+use x11rb::protocol::xinerama::{self, ConnectionExt as _};
+
+use crate::layout::Rect;
+use crate::randr::Output;
+
+/// Экраны Xinerama как выходы `xinerama-N`; первый считается основным.
+/// Без активного Xinerama возвращается пустой список.
+pub fn outputs(conn: &impl Connection) -> Result<Vec<Output>, ReplyOrIdError> {
+    if conn
+        .extension_information(xinerama::X11_EXTENSION_NAME)?
+        .is_none()
+        || conn.xinerama_is_active()?.reply()?.state == 0
+    {
+        return Ok(Vec::new());
+    }
+    let screens = conn.xinerama_query_screens()?.reply()?.screen_info;
+    let mut outputs: Vec<Output> = Vec::new();
+    for (i, screen) in screens.iter().enumerate() {
+        let area = Rect {
+            x: screen.x_org.into(),
+            y: screen.y_org.into(),
+            width: screen.width.into(),
+            height: screen.height.into(),
+        };
+        if outputs.iter().any(|o| o.area == area) {
+            continue;
+        }
+        outputs.push(Output {
+            name: format!("xinerama-{}", i),
+            area,
+            primary: i == 0,
+            dpi: None,
+        });
+    }
+    Ok(outputs)
+}